@@ -21,6 +21,7 @@
 #include <speechapi_c_result.h>
 #include <speechapi_c_session.h>
 #include <speechapi_c_connection.h>
+#include <speechapi_c_grammar.h>
 #include <speechapi_c_speech_config.h>
 #include <speechapi_c_synthesizer.h>
 #include <spxerror.h>
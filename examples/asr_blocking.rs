@@ -0,0 +1,44 @@
+use std::{env, thread};
+
+use log::{debug, info};
+
+use microsoft_speech::{
+    audio::AudioConfig,
+    BlockingRecognizer,
+    EventIterator,
+    PropertyId,
+    recognizer::SpeechRecognizer,
+    SpeechConfig,
+};
+
+fn main() {
+    env::set_var("RUST_BACKTRACE", "1");
+    env::set_var("RUST_LOG", "trace");
+    env_logger::init();
+    info!("Start blocking ASR test...");
+
+    let mut sc =
+        SpeechConfig::from_subscription("YourSubscriptionKey", "YourServiceRegion").unwrap();
+    sc.set(PropertyId::SpeechServiceConnectionRecoLanguage, "zh-CN")
+        .unwrap();
+
+    let ac = AudioConfig::from_wav_file_input("chinese_test.wav").unwrap();
+    let mut recognizer = SpeechRecognizer::from_config(sc, Some(ac)).unwrap();
+    let recognized = EventIterator::new(recognizer.connect_recognized(None));
+    let session_stopped = EventIterator::new(recognizer.connect_session_stopped(None));
+    recognizer.start_continuous_recognition_blocking().unwrap();
+
+    let printer = thread::spawn(move || {
+        for e in recognized {
+            let r = e.result().unwrap();
+            debug!("id: {}, reason: {:?}, text: {}", r.id().unwrap(), r.reason().unwrap(), r.text().unwrap());
+        }
+    });
+
+    // wait until the whole file has been recognized
+    session_stopped.take(1).for_each(drop);
+    recognizer.stop_continuous_recognition_blocking().unwrap();
+    drop(recognizer);
+    printer.join().unwrap();
+    info!("done");
+}
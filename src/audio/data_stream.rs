@@ -0,0 +1,143 @@
+use std::cmp;
+use std::ffi::CString;
+use std::io;
+
+use num::FromPrimitive;
+
+use crate::convert_err;
+use crate::CancellationErrorCode;
+use crate::CancellationReason;
+use crate::SmartHandle;
+use crate::speech_api::*;
+use crate::SpxError;
+use crate::SPXHANDLE_INVALID;
+use crate::synthesizer::SpeechSynthesisCancellationDetails;
+use crate::synthesizer::SpeechSynthesisResult;
+
+enum_from_primitive! {
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StreamStatus {
+    Unknown = 0,
+    /// No data has been received yet.
+    NoData = 1,
+    /// Some of the data is available, synthesis is still in progress.
+    PartialData = 2,
+    /// All of the data is available.
+    AllData = 3,
+    /// Synthesis has been canceled, see `AudioDataStream::cancellation_details`.
+    Canceled = 4,
+}
+}
+
+/// Seekable in-memory view of the audio of a `SpeechSynthesisResult`.
+#[derive(Debug)]
+pub struct AudioDataStream {
+    handle: SmartHandle<SPXAUDIOSTREAMHANDLE>,
+}
+
+impl AudioDataStream {
+    pub fn from_result(result: &SpeechSynthesisResult) -> Result<AudioDataStream, SpxError> {
+        let mut handle = SPXHANDLE_INVALID;
+        unsafe {
+            convert_err(audio_data_stream_create_from_result(&mut handle, result.get_handle()))?;
+        }
+        Ok(AudioDataStream {
+            handle: SmartHandle::create("AudioDataStream", handle, audio_data_stream_release),
+        })
+    }
+
+    pub fn status(&self) -> Result<StreamStatus, SpxError> {
+        let code = crate::spx_populate(self.get_handle(), audio_data_stream_get_status)?;
+        Ok(StreamStatus::from_u32(code).expect("unknown status"))
+    }
+
+    /// Whether `size` bytes can be read from the current position.
+    pub fn can_read_data(&self, size: u32) -> bool {
+        unsafe { audio_data_stream_can_read_data(self.get_handle(), size) }
+    }
+
+    pub fn can_read_data_from_position(&self, size: u32, position: u32) -> bool {
+        unsafe { audio_data_stream_can_read_data_from_position(self.get_handle(), size, position) }
+    }
+
+    /// Reads from the current position and advances it, returns 0 when there is no more data.
+    pub fn read_data(&mut self, buf: &mut [u8]) -> Result<usize, SpxError> {
+        let mut filled: u32 = 0;
+        unsafe {
+            convert_err(audio_data_stream_read(
+                self.get_handle(),
+                buf.as_mut_ptr(),
+                clamp_len(buf),
+                &mut filled,
+            ))?;
+        }
+        Ok(filled as usize)
+    }
+
+    /// Reads from `position`, the current position is moved to the end of the read data.
+    pub fn read_data_from_position(&mut self, buf: &mut [u8], position: u32) -> Result<usize, SpxError> {
+        let mut filled: u32 = 0;
+        unsafe {
+            convert_err(audio_data_stream_read_from_position(
+                self.get_handle(),
+                buf.as_mut_ptr(),
+                clamp_len(buf),
+                position,
+                &mut filled,
+            ))?;
+        }
+        Ok(filled as usize)
+    }
+
+    pub fn position(&self) -> Result<u32, SpxError> {
+        crate::spx_populate(self.get_handle(), audio_data_stream_get_position)
+    }
+
+    pub fn set_position(&mut self, position: u32) -> Result<(), SpxError> {
+        unsafe {
+            convert_err(audio_data_stream_set_position(self.get_handle(), position))
+        }
+    }
+
+    /// Writes the whole audio, from the beginning, to a wave file.
+    pub fn save_to_wave_file<NM: AsRef<str>>(&self, file_name: NM) -> Result<(), SpxError> {
+        let c_file_name = CString::new(file_name.as_ref())?;
+        unsafe {
+            convert_err(audio_data_stream_save_to_wave_file(self.get_handle(), c_file_name.as_ptr()))
+        }
+    }
+
+    /// Only meaningful if `status()` is `StreamStatus::Canceled`.
+    pub fn cancellation_details(&self) -> Result<SpeechSynthesisCancellationDetails, SpxError> {
+        let reason = {
+            let code = crate::spx_populate(self.get_handle(), audio_data_stream_get_reason_canceled)?;
+            CancellationReason::from_u32(code).expect("unknown reason")
+        };
+        let err_code = {
+            let code = crate::spx_populate(self.get_handle(), audio_data_stream_get_canceled_error_code)?;
+            CancellationErrorCode::from_u32(code).expect("unknown code")
+        };
+        Ok(SpeechSynthesisCancellationDetails {
+            reason,
+            err_code,
+        })
+    }
+
+    #[inline(always)]
+    pub fn get_handle(&self) -> SPXAUDIOSTREAMHANDLE {
+        self.handle.get()
+    }
+}
+
+impl io::Read for AudioDataStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.read_data(buf).map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))
+    }
+}
+
+unsafe impl Send for AudioDataStream {}
+
+#[inline(always)]
+fn clamp_len(buf: &[u8]) -> u32 {
+    cmp::min(buf.len(), u32::MAX as usize) as u32
+}
@@ -1,4 +1,8 @@
 use std::ffi::CString;
+use std::fs::File;
+use std::io::BufReader;
+use std::io::Read;
+use std::path::Path;
 
 use crate::convert_err;
 use crate::SmartHandle;
@@ -6,18 +10,37 @@ use crate::speech_api::*;
 use crate::SpxError;
 use crate::SPXHANDLE_INVALID;
 
+pub use self::data_stream::AudioDataStream;
+pub use self::data_stream::StreamStatus;
+pub use self::pump::PumpRead;
+pub use self::pump::PumpStream;
+pub use self::read_callback::ReadCallback;
+pub use self::read_callback::WavReadCallback;
+pub use self::stream::AsyncPullAudioOutputStreamReader;
 pub use self::stream::AudioInputStream;
+pub use self::stream::AudioOutputStream;
 pub use self::stream::AudioStreamSink;
 pub use self::stream::PullAudioInputStreamCallback;
+pub use self::stream::PullAudioOutputStreamReader;
+pub use self::stream::PushAudioOutputStreamCallback;
 pub use self::stream_format::AudioStreamFormat;
+pub use self::stream_format::ContainerFormat;
+pub use self::wav::SampleEncoding;
+pub use self::wav::WaveFormat;
 
+mod data_stream;
+mod pump;
+mod read_callback;
 mod stream;
 mod stream_format;
+pub(crate) mod wav;
 
 pub struct AudioConfig {
     handle: SmartHandle<SPXAUDIOCONFIGHANDLE>,
     #[allow(unused)]
     stream: Option<Box<dyn AudioInputStream>>,
+    #[allow(unused)]
+    output_stream: Option<Box<dyn AudioOutputStream>>,
 }
 
 impl AudioConfig {
@@ -32,6 +55,7 @@ impl AudioConfig {
         let result = AudioConfig {
             handle: SmartHandle::create("AudioConfig", handle, audio_config_release),
             stream: Some(stream),
+            output_stream: None,
         };
         Ok(result)
     }
@@ -48,10 +72,28 @@ impl AudioConfig {
         let result = AudioConfig {
             handle: SmartHandle::create("AudioConfig", handle, audio_config_release),
             stream: None,
+            output_stream: None,
         };
         Ok(result)
     }
 
+    /// Parses the wave header in Rust and streams the samples, converted to 16 bit PCM if necessary,
+    /// through a pull stream.
+    pub fn from_wav_reader<R: Read + Send + 'static>(reader: R) -> Result<AudioConfig, SpxError> {
+        let callback = WavReadCallback::new(reader)?;
+        let format = callback.stream_format()?;
+        let stream = <dyn AudioInputStream>::create_pull_stream(callback, Some(format))?;
+        AudioConfig::from_stream_input(stream)
+    }
+
+    /// Opens the file and reads it with `from_wav_reader`. Unlike `from_wav_file_input`, where the
+    /// Speech SDK opens the file itself and only accepts plain PCM, `WAVE_FORMAT_EXTENSIBLE` and float
+    /// samples are accepted, and header errors are returned here instead of by the recognizer.
+    pub fn from_wav_reader_file<P: AsRef<Path>>(path: P) -> Result<AudioConfig, SpxError> {
+        let file = File::open(path)?;
+        AudioConfig::from_wav_reader(BufReader::new(file))
+    }
+
     pub fn output_from_default_speaker() -> Result<AudioConfig, SpxError> {
         let mut handle = SPXHANDLE_INVALID;
         unsafe {
@@ -60,6 +102,38 @@ impl AudioConfig {
         Ok(AudioConfig {
             handle: SmartHandle::create("AudioConfig", handle, audio_config_release),
             stream: None,
+            output_stream: None,
+        })
+    }
+
+    pub fn output_to_wav_file<NM: AsRef<str>>(file_name: NM) -> Result<AudioConfig, SpxError> {
+        let mut handle = SPXHANDLE_INVALID;
+        let c_file_name = CString::new(file_name.as_ref())?;
+        unsafe {
+            convert_err(audio_config_create_audio_output_from_wav_file_name(
+                &mut handle,
+                c_file_name.as_ptr(),
+            ))?;
+        }
+        Ok(AudioConfig {
+            handle: SmartHandle::create("AudioConfig", handle, audio_config_release),
+            stream: None,
+            output_stream: None,
+        })
+    }
+
+    pub fn output_to_stream(stream: Box<dyn AudioOutputStream>) -> Result<AudioConfig, SpxError> {
+        let mut handle = SPXHANDLE_INVALID;
+        unsafe {
+            convert_err(audio_config_create_audio_output_from_stream(
+                &mut handle,
+                stream.get_handle(),
+            ))?;
+        }
+        Ok(AudioConfig {
+            handle: SmartHandle::create("AudioConfig", handle, audio_config_release),
+            stream: None,
+            output_stream: Some(stream),
         })
     }
 
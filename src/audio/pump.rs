@@ -0,0 +1,115 @@
+use futures::prelude::*;
+use tokio::io::AsyncRead;
+
+use crate::audio::AudioStreamSink;
+use crate::SpxError;
+
+const DEFAULT_PUMP_BUFF_SIZE: usize = 4096;
+
+/// Future returned by `AudioStreamSink::pump_stream`.
+#[must_use = "futures do nothing unless polled"]
+pub struct PumpStream<S, K> {
+    stream: S,
+    sink: K,
+    done: bool,
+}
+
+impl<S, K> PumpStream<S, K> {
+    pub(crate)
+    fn new(stream: S, sink: K) -> PumpStream<S, K> {
+        PumpStream {
+            stream,
+            sink,
+            done: false,
+        }
+    }
+}
+
+impl<S, K> Future for PumpStream<S, K>
+    where S: Stream,
+          S::Item: AsRef<[u8]>,
+          S::Error: Into<SpxError>,
+          K: AudioStreamSink {
+    type Item = ();
+    type Error = SpxError;
+
+    fn poll(&mut self) -> Poll<(), SpxError> {
+        if self.done {
+            return Ok(Async::Ready(()));
+        }
+        loop {
+            let result = match self.stream.poll() {
+                Ok(Async::Ready(Some(buf))) => self.sink.write(buf),
+                Ok(Async::Ready(None)) => {
+                    self.done = true;
+                    self.sink.close()?;
+                    return Ok(Async::Ready(()));
+                }
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = result {
+                return Err(close_on_error(&mut self.sink, &mut self.done, e));
+            }
+        }
+    }
+}
+
+/// Future returned by `AudioStreamSink::pump_reader`.
+#[must_use = "futures do nothing unless polled"]
+pub struct PumpRead<R, K> {
+    reader: R,
+    sink: K,
+    buf: Vec<u8>,
+    done: bool,
+}
+
+impl<R, K> PumpRead<R, K> {
+    pub(crate)
+    fn new(reader: R, sink: K) -> PumpRead<R, K> {
+        PumpRead {
+            reader,
+            sink,
+            buf: vec![0u8; DEFAULT_PUMP_BUFF_SIZE],
+            done: false,
+        }
+    }
+}
+
+impl<R, K> Future for PumpRead<R, K>
+    where R: AsyncRead,
+          K: AudioStreamSink {
+    type Item = ();
+    type Error = SpxError;
+
+    fn poll(&mut self) -> Poll<(), SpxError> {
+        if self.done {
+            return Ok(Async::Ready(()));
+        }
+        loop {
+            let result = match self.reader.poll_read(&mut self.buf) {
+                Ok(Async::Ready(0)) => {
+                    self.done = true;
+                    self.sink.close()?;
+                    return Ok(Async::Ready(()));
+                }
+                Ok(Async::Ready(n)) => self.sink.write(&self.buf[..n]),
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(e) => Err(SpxError::from(e)),
+            };
+            if let Err(e) = result {
+                return Err(close_on_error(&mut self.sink, &mut self.done, e));
+            }
+        }
+    }
+}
+
+/// The sink is closed so that a waiting recognizer sees the end of the audio,
+/// the original error is returned.
+fn close_on_error(sink: &mut impl AudioStreamSink, done: &mut bool, err: SpxError) -> SpxError {
+    *done = true;
+    if let Err(e) = sink.close() {
+        warn!("failed to close audio stream sink after error: {}", e);
+    }
+    err
+}
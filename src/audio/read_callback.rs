@@ -0,0 +1,199 @@
+use std::cmp;
+use std::io;
+use std::io::Read;
+
+use crate::audio::AudioStreamFormat;
+use crate::audio::PullAudioInputStreamCallback;
+use crate::audio::wav;
+use crate::audio::WaveFormat;
+use crate::SpxError;
+
+/// Pull stream callback reading raw audio from any reader, e.g. a file, a pipe or `Cursor<Vec<u8>>`.
+/// The end of the audio is signaled at EOF or on the first read error.
+pub struct ReadCallback<R> {
+    reader: Option<R>,
+}
+
+impl<R: Read + Send> ReadCallback<R> {
+    pub fn new(reader: R) -> ReadCallback<R> {
+        ReadCallback {
+            reader: Some(reader),
+        }
+    }
+}
+
+impl<R: Read + Send> PullAudioInputStreamCallback for ReadCallback<R> {
+    fn read(&mut self, data_buffer: &mut [u8]) -> usize {
+        match self.reader {
+            Some(ref mut reader) => read_or_eof(reader, data_buffer),
+            None => 0,
+        }
+    }
+
+    fn close(&mut self) {
+        self.reader = None;
+    }
+}
+
+/// Like `ReadCallback`, the wave header is consumed first and the audio format is taken from it.
+/// Samples which are not 16 bit PCM are converted while reading.
+pub struct WavReadCallback<R> {
+    reader: Option<R>,
+    format: WaveFormat,
+    remaining: Option<u32>,
+    /// Source samples for conversion, starts with `pending` bytes of an incomplete sample.
+    scratch: Vec<u8>,
+    pending: usize,
+    /// Converted bytes which did not fit into the previous buffer.
+    converted: Vec<u8>,
+}
+
+impl<R: Read + Send> WavReadCallback<R> {
+    /// Fails if the header is malformed or the sample format is not supported.
+    pub fn new(mut reader: R) -> Result<WavReadCallback<R>, SpxError> {
+        let (format, remaining) = wav::read_header(&mut reader)?;
+        Ok(WavReadCallback {
+            reader: Some(reader),
+            format,
+            remaining,
+            scratch: Vec::new(),
+            pending: 0,
+            converted: Vec::new(),
+        })
+    }
+
+    /// Format of the file, see `stream_format` for the format of the audio returned by `read`.
+    pub fn wave_format(&self) -> WaveFormat {
+        self.format
+    }
+
+    /// Format to pass to `AudioInputStream::create_pull_stream` along with this callback.
+    pub fn stream_format(&self) -> Result<AudioStreamFormat, SpxError> {
+        self.format.to_stream_format()
+    }
+
+    /// Reads samples of the data chunk, chunks after it must not be read as audio.
+    fn read_data(reader: &mut R, remaining: &mut Option<u32>, buf: &mut [u8]) -> usize {
+        let len = match *remaining {
+            Some(remaining) => cmp::min(buf.len(), remaining as usize),
+            None => buf.len(),
+        };
+        let n = read_or_eof(reader, &mut buf[..len]);
+        if let Some(ref mut remaining) = *remaining {
+            *remaining -= n as u32;
+        }
+        n
+    }
+
+    fn read_converted(&mut self, data_buffer: &mut [u8]) -> usize {
+        if !self.converted.is_empty() {
+            let n = cmp::min(data_buffer.len(), self.converted.len());
+            data_buffer[..n].copy_from_slice(&self.converted[..n]);
+            self.converted.drain(..n);
+            return n;
+        }
+        if data_buffer.len() >= 2 {
+            return self.convert(data_buffer);
+        }
+        // a converted sample does not fit, 0 would end the stream
+        let mut sample = [0u8; 2];
+        let written = self.convert(&mut sample);
+        let n = cmp::min(data_buffer.len(), written);
+        data_buffer[..n].copy_from_slice(&sample[..n]);
+        self.converted.extend_from_slice(&sample[n..written]);
+        n
+    }
+
+    /// Converts as many samples as fit into `data_buffer`, which must hold at least one.
+    fn convert(&mut self, data_buffer: &mut [u8]) -> usize {
+        let reader = match self.reader {
+            Some(ref mut reader) => reader,
+            None => return 0,
+        };
+        let sample_size = self.format.bytes_per_sample();
+        let want = data_buffer.len() / 2 * sample_size;
+        self.scratch.resize(cmp::max(want, self.pending), 0);
+        let mut filled = self.pending;
+        // at least one whole sample, otherwise 0 would end the stream
+        while filled < sample_size {
+            let n = Self::read_data(reader, &mut self.remaining, &mut self.scratch[filled..want]);
+            if n == 0 {
+                break;
+            }
+            filled += n;
+        }
+        let whole = filled / sample_size * sample_size;
+        let written = wav::convert_samples(&self.format, &self.scratch[..whole], data_buffer);
+        self.scratch.copy_within(whole..filled, 0);
+        self.pending = filled - whole;
+        written
+    }
+}
+
+impl<R: Read + Send> PullAudioInputStreamCallback for WavReadCallback<R> {
+    fn read(&mut self, data_buffer: &mut [u8]) -> usize {
+        if self.format.needs_conversion() {
+            return self.read_converted(data_buffer);
+        }
+        match self.reader {
+            Some(ref mut reader) => Self::read_data(reader, &mut self.remaining, data_buffer),
+            None => 0,
+        }
+    }
+
+    fn close(&mut self) {
+        self.reader = None;
+    }
+}
+
+fn read_or_eof<R: Read>(reader: &mut R, buf: &mut [u8]) -> usize {
+    loop {
+        match reader.read(buf) {
+            Ok(n) => return n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                warn!("failed to read audio input, err: {}", e);
+                return 0;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    // 8 bit mono, 3 samples
+    const WAVE: &[u8] = b"RIFF\x27\0\0\0WAVEfmt \x10\0\0\0\x01\0\x01\0\x80\x3e\0\0\x80\x3e\0\0\x01\0\x08\0data\x03\0\0\0\x00\x80\xff";
+
+    fn read_all(callback: &mut impl PullAudioInputStreamCallback, buf_len: usize) -> Vec<u8> {
+        let mut result = Vec::new();
+        let mut buf = vec![0u8; buf_len];
+        loop {
+            let n = callback.read(&mut buf);
+            if n == 0 {
+                return result;
+            }
+            result.extend_from_slice(&buf[..n]);
+        }
+    }
+
+    #[test]
+    fn converts_samples_for_any_buffer_size() {
+        let expected = vec![0x00, 0x80, 0x00, 0x00, 0x00, 0x7f];
+        for &buf_len in &[1, 2, 3, 4, 64] {
+            let mut callback = WavReadCallback::new(Cursor::new(WAVE)).unwrap();
+            assert_eq!(read_all(&mut callback, buf_len), expected, "buffer of {} bytes", buf_len);
+        }
+    }
+
+    #[test]
+    fn stops_at_the_end_of_the_data_chunk() {
+        let mut wave = WAVE.to_vec();
+        wave.extend_from_slice(b"LIST\x02\0\0\0ab");
+        let mut callback = WavReadCallback::new(Cursor::new(wave)).unwrap();
+        assert_eq!(read_all(&mut callback, 64).len(), 6);
+    }
+}
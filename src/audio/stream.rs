@@ -1,27 +1,79 @@
+use std::cmp;
 use std::ffi::c_void;
+use std::io;
+use std::io::Read;
 use std::ops::Deref;
 use std::ops::DerefMut;
+use std::ptr;
 use std::slice;
 use std::sync::Arc;
 use std::sync::Weak;
+use std::thread;
+
+use futures::prelude::*;
+use futures::sync::mpsc::{channel, Receiver};
+use tokio::io::AsyncRead;
 
 use crate::audio::AudioStreamFormat;
+use crate::audio::PumpRead;
+use crate::audio::PumpStream;
 use crate::convert_err;
 use crate::SmartHandle;
 use crate::speech_api::*;
 use crate::SpxError;
 use crate::SPXHANDLE_INVALID;
 
+const DEFAULT_READ_CHUNK_SIZE: usize = 4096;
+const DEFAULT_READ_CH_BUFF_SIZE: usize = 16;
+
 pub trait AudioStreamSink: Send {
     fn write(&mut self, buf: impl AsRef<[u8]>) -> Result<(), SpxError>;
 
     fn close(&mut self) -> Result<(), SpxError>;
+
+    /// Writes every buffer of `stream` into this sink, the sink is closed at the end of the stream
+    /// or on the first error.
+    fn pump_stream<S>(self, stream: S) -> PumpStream<S, Self>
+        where S: Stream,
+              S::Item: AsRef<[u8]>,
+              S::Error: Into<SpxError>,
+              Self: Sized {
+        PumpStream::new(stream, self)
+    }
+
+    /// Like `pump_stream`, reading from `reader` until EOF.
+    fn pump_reader<R>(self, reader: R) -> PumpRead<R, Self>
+        where R: AsyncRead,
+              Self: Sized {
+        PumpRead::new(reader, self)
+    }
 }
 
 pub trait AudioInputStream: Send {
     fn get_handle(&self) -> SPXAUDIOSTREAMHANDLE;
 }
 
+pub trait AudioOutputStream: Send {
+    fn get_handle(&self) -> SPXAUDIOSTREAMHANDLE;
+}
+
+impl dyn AudioOutputStream {
+    /// Audio is pushed into `callback` as soon as it is synthesized.
+    pub fn create_push_stream<CB>(callback: CB, format: Option<AudioStreamFormat>) -> Result<Box<dyn AudioOutputStream>, SpxError>
+        where CB: PushAudioOutputStreamCallback + 'static {
+        Ok(Box::new(PushAudioOutputStream::create(format, callback)?))
+    }
+
+    /// Audio is buffered by the Speech SDK until it is read from the returned reader.
+    pub fn create_pull_stream(format: Option<AudioStreamFormat>) -> Result<(Box<dyn AudioOutputStream>, PullAudioOutputStreamReader), SpxError> {
+        let stream = PullAudioOutputStream::create(format)?;
+        let reader = PullAudioOutputStreamReader {
+            handle: stream.handle.clone(),
+        };
+        Ok((Box::new(stream), reader))
+    }
+}
+
 impl AudioInputStream {
     pub fn create_push_stream(format: Option<AudioStreamFormat>) -> Result<(Box<dyn AudioInputStream>, impl AudioStreamSink), SpxError> {
         let stream = PushAudioInputStream::create(format)?;
@@ -38,23 +90,20 @@ impl AudioInputStream {
 }
 
 #[derive(Debug)]
-struct BaseAudioInputStream {
+struct BaseAudioStream {
     handle: Arc<SmartHandle<SPXAUDIOSTREAMHANDLE>>,
     format: AudioStreamFormat,
 }
 
-impl BaseAudioInputStream {
+impl BaseAudioStream {
     fn create(name: &'static str,
-              format: Option<AudioStreamFormat>,
-              create_fn: unsafe extern "C" fn(*mut SPXAUDIOSTREAMHANDLE, SPXAUDIOSTREAMFORMATHANDLE) -> SPXHR) -> Result<BaseAudioInputStream, SpxError> {
-        let format = format
-            .map(|x| Ok(x))
-            .unwrap_or_else(|| { AudioStreamFormat::get_default_input_format() })?;
+              format: AudioStreamFormat,
+              create_fn: unsafe extern "C" fn(*mut SPXAUDIOSTREAMHANDLE, SPXAUDIOSTREAMFORMATHANDLE) -> SPXHR) -> Result<BaseAudioStream, SpxError> {
         let mut handle = SPXHANDLE_INVALID;
         unsafe {
             convert_err(create_fn(&mut handle, format.get_handle()))?;
         }
-        let result = BaseAudioInputStream {
+        let result = BaseAudioStream {
             handle: Arc::new(SmartHandle::create(name, handle, audio_stream_release)),
             format,
         };
@@ -62,17 +111,35 @@ impl BaseAudioInputStream {
     }
 }
 
+#[inline]
+fn input_format_or_default(format: Option<AudioStreamFormat>) -> Result<AudioStreamFormat, SpxError> {
+    format
+        .map(Ok)
+        .unwrap_or_else(|| { AudioStreamFormat::get_default_input_format() })
+}
+
+#[inline]
+fn output_format_or_default(format: Option<AudioStreamFormat>) -> Result<AudioStreamFormat, SpxError> {
+    format
+        .map(Ok)
+        .unwrap_or_else(|| { AudioStreamFormat::get_default_output_format() })
+}
+
 // PushAudioInputStream
 
 #[derive(Debug)]
 struct PushAudioInputStream {
-    base: BaseAudioInputStream,
+    base: BaseAudioStream,
 }
 
 impl PushAudioInputStream {
     fn create(format: Option<AudioStreamFormat>) -> Result<PushAudioInputStream, SpxError> {
         Ok(PushAudioInputStream {
-            base: BaseAudioInputStream::create("PushAudioInputStream", format, audio_stream_create_push_audio_input_stream)?,
+            base: BaseAudioStream::create(
+                "PushAudioInputStream",
+                input_format_or_default(format)?,
+                audio_stream_create_push_audio_input_stream,
+            )?,
         })
     }
 }
@@ -85,15 +152,15 @@ impl AudioInputStream for PushAudioInputStream {
 }
 
 impl Deref for PushAudioInputStream {
-    type Target = BaseAudioInputStream;
+    type Target = BaseAudioStream;
 
-    fn deref(&self) -> &BaseAudioInputStream {
+    fn deref(&self) -> &BaseAudioStream {
         &self.base
     }
 }
 
 impl DerefMut for PushAudioInputStream {
-    fn deref_mut(&mut self) -> &mut BaseAudioInputStream {
+    fn deref_mut(&mut self) -> &mut BaseAudioStream {
         &mut self.base
     }
 }
@@ -159,14 +226,18 @@ pub trait PullAudioInputStreamCallback: Send {
 }
 
 struct PullAudioInputStream<CB> {
-    base: BaseAudioInputStream,
+    base: BaseAudioStream,
     callback: Box<CB>,
 }
 
 impl<CB> PullAudioInputStream<CB> where CB: PullAudioInputStreamCallback + 'static {
     fn create(format: Option<AudioStreamFormat>, callback: CB) -> Result<PullAudioInputStream<CB>, SpxError> {
         let mut result = PullAudioInputStream {
-            base: BaseAudioInputStream::create("PullAudioInputStream", format, audio_stream_create_pull_audio_input_stream)?,
+            base: BaseAudioStream::create(
+                "PullAudioInputStream",
+                input_format_or_default(format)?,
+                audio_stream_create_pull_audio_input_stream,
+            )?,
             callback: Box::new(callback),
         };
 
@@ -200,9 +271,9 @@ impl<CB> PullAudioInputStream<CB> where CB: PullAudioInputStreamCallback + 'stat
 }
 
 impl<CB> Deref for PullAudioInputStream<CB> {
-    type Target = BaseAudioInputStream;
+    type Target = BaseAudioStream;
 
-    fn deref(&self) -> &BaseAudioInputStream {
+    fn deref(&self) -> &BaseAudioStream {
         &self.base
     }
 }
@@ -215,3 +286,344 @@ impl<CB: Send> AudioInputStream for PullAudioInputStream<CB> {
 }
 
 unsafe impl<CB: Send> Send for PullAudioInputStream<CB> {}
+
+// PushAudioOutputStream
+
+pub trait PushAudioOutputStreamCallback: Send {
+    /// Returns the number of bytes consumed.
+    fn write(&mut self, data_buffer: &[u8]) -> usize;
+    fn close(&mut self);
+}
+
+struct PushAudioOutputStream<CB> {
+    base: BaseAudioStream,
+    callback: Box<CB>,
+}
+
+impl<CB> PushAudioOutputStream<CB> where CB: PushAudioOutputStreamCallback + 'static {
+    fn create(format: Option<AudioStreamFormat>, callback: CB) -> Result<PushAudioOutputStream<CB>, SpxError> {
+        let mut result = PushAudioOutputStream {
+            base: BaseAudioStream::create(
+                "PushAudioOutputStream",
+                output_format_or_default(format)?,
+                audio_stream_create_push_audio_output_stream,
+            )?,
+            callback: Box::new(callback),
+        };
+
+        unsafe {
+            let cb_ptr = &mut *result.callback as *mut _ as *mut c_void;
+            convert_err(push_audio_output_stream_set_callbacks(
+                result.get_handle(),
+                cb_ptr,
+                Some(Self::cb_write),
+                Some(Self::cb_close),
+            ))?;
+        }
+
+        Ok(result)
+    }
+
+    extern "C" fn cb_write(
+        pv_ctx: *mut ::std::os::raw::c_void,
+        buff: *mut u8,
+        size: u32,
+    ) -> ::std::os::raw::c_int {
+        let cb = unsafe { &mut *(pv_ctx as *mut CB) };
+        let buff = unsafe { slice::from_raw_parts(buff, size as usize) };
+        cb.write(buff) as i32
+    }
+
+    extern "C" fn cb_close(pv_ctx: *mut ::std::os::raw::c_void) {
+        let cb = unsafe { &mut *(pv_ctx as *mut CB) };
+        cb.close();
+    }
+}
+
+// set when the stream is dropped, audio written after that is discarded
+extern "C" fn cb_write_detached(
+    _pv_ctx: *mut ::std::os::raw::c_void,
+    _buff: *mut u8,
+    size: u32,
+) -> ::std::os::raw::c_int {
+    size as i32
+}
+
+extern "C" fn cb_close_detached(_pv_ctx: *mut ::std::os::raw::c_void) {}
+
+impl<CB> Drop for PushAudioOutputStream<CB> {
+    fn drop(&mut self) {
+        // the native stream may outlive `callback` if the Speech SDK still holds a reference to it
+        unsafe {
+            if audio_stream_is_handle_valid(self.base.handle.get()) {
+                let hr = push_audio_output_stream_set_callbacks(
+                    self.base.handle.get(),
+                    ptr::null_mut(),
+                    Some(cb_write_detached),
+                    Some(cb_close_detached),
+                );
+                if let Err(e) = convert_err(hr) {
+                    error!("can not detach push audio output stream callbacks, err: {}", e);
+                }
+            }
+        }
+    }
+}
+
+impl<CB> Deref for PushAudioOutputStream<CB> {
+    type Target = BaseAudioStream;
+
+    fn deref(&self) -> &BaseAudioStream {
+        &self.base
+    }
+}
+
+impl<CB: Send> AudioOutputStream for PushAudioOutputStream<CB> {
+    #[inline(always)]
+    fn get_handle(&self) -> SPXAUDIOSTREAMHANDLE {
+        self.base.handle.get()
+    }
+}
+
+unsafe impl<CB: Send> Send for PushAudioOutputStream<CB> {}
+
+// PullAudioOutputStream
+
+#[derive(Debug)]
+struct PullAudioOutputStream {
+    base: BaseAudioStream,
+}
+
+impl PullAudioOutputStream {
+    fn create(format: Option<AudioStreamFormat>) -> Result<PullAudioOutputStream, SpxError> {
+        Ok(PullAudioOutputStream {
+            base: BaseAudioStream::create(
+                "PullAudioOutputStream",
+                output_format_or_default(format)?,
+                audio_stream_create_pull_audio_output_stream,
+            )?,
+        })
+    }
+}
+
+impl Deref for PullAudioOutputStream {
+    type Target = BaseAudioStream;
+
+    fn deref(&self) -> &BaseAudioStream {
+        &self.base
+    }
+}
+
+impl AudioOutputStream for PullAudioOutputStream {
+    #[inline(always)]
+    fn get_handle(&self) -> SPXAUDIOSTREAMHANDLE {
+        self.base.handle.get()
+    }
+}
+
+unsafe impl Send for PullAudioOutputStream {}
+
+/// Blocking reader of a pull audio output stream,
+/// `read` waits for synthesized audio and returns 0 once the stream has ended.
+pub struct PullAudioOutputStreamReader {
+    handle: Arc<SmartHandle<SPXAUDIOSTREAMHANDLE>>,
+}
+
+impl PullAudioOutputStreamReader {
+    pub fn read_audio(&mut self, buf: &mut [u8]) -> Result<usize, SpxError> {
+        let size = cmp::min(buf.len(), u32::MAX as usize) as u32;
+        let mut filled: u32 = 0;
+        unsafe {
+            convert_err(pull_audio_output_stream_read(self.handle.get(), buf.as_mut_ptr(), size, &mut filled))?;
+        }
+        Ok(filled as usize)
+    }
+
+    /// Moves the blocking reads to a background thread, the audio is delivered
+    /// through a channel of `buff_size` chunks.
+    ///
+    /// The thread ends when the stream has ended, on the first error, or with the first chunk read after
+    /// the returned reader has been dropped. A read of the Speech SDK only returns once audio is available
+    /// or the stream is closed, i.e. the synthesizer using it is dropped, so the thread lives at most as long
+    /// as that synthesizer and its pending speak handles. See `AsyncPullAudioOutputStreamReader::join`.
+    pub fn into_async(self, buff_size: Option<usize>) -> AsyncPullAudioOutputStreamReader {
+        let (mut s, r) = channel(buff_size.unwrap_or(DEFAULT_READ_CH_BUFF_SIZE));
+        let mut reader = self;
+        let thread = thread::spawn(move || {
+            loop {
+                let mut chunk = vec![0u8; DEFAULT_READ_CHUNK_SIZE];
+                let item = reader.read_audio(&mut chunk).map(|n| {
+                    chunk.truncate(n);
+                    chunk
+                });
+                let done = item.as_ref().map(|x| x.is_empty()).unwrap_or(true);
+                s = match s.send(item).wait() {
+                    Ok(s) => s,
+                    // receiver has been dropped
+                    Err(_) => break,
+                };
+                if done {
+                    break;
+                }
+            }
+        });
+        AsyncPullAudioOutputStreamReader {
+            receiver: r,
+            thread,
+            pending: io::Cursor::new(Vec::new()),
+            done: false,
+        }
+    }
+}
+
+impl Read for PullAudioOutputStreamReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.read_audio(buf).map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))
+    }
+}
+
+unsafe impl Send for PullAudioOutputStreamReader {}
+
+/// Non-blocking counterpart of `PullAudioOutputStreamReader`, either a stream of audio chunks
+/// or an `AsyncRead`, e.g. for `tokio::io::copy`. Both end with the synthesized audio.
+pub struct AsyncPullAudioOutputStreamReader {
+    receiver: Receiver<Result<Vec<u8>, SpxError>>,
+    thread: thread::JoinHandle<()>,
+    /// Rest of the chunk which did not fit into the buffer of the last `read`.
+    pending: io::Cursor<Vec<u8>>,
+    done: bool,
+}
+
+impl AsyncPullAudioOutputStreamReader {
+    /// Stops receiving audio and waits for the background thread. This blocks until its pending read
+    /// of the Speech SDK returns, i.e. until more audio is synthesized or the synthesizer is dropped.
+    pub fn join(self) -> thread::Result<()> {
+        let AsyncPullAudioOutputStreamReader { receiver, thread, .. } = self;
+        drop(receiver);
+        thread.join()
+    }
+}
+
+impl Stream for AsyncPullAudioOutputStreamReader {
+    type Item = Vec<u8>;
+    type Error = SpxError;
+
+    fn poll(&mut self) -> Poll<Option<Vec<u8>>, SpxError> {
+        let pos = self.pending.position() as usize;
+        if pos < self.pending.get_ref().len() {
+            let mut chunk = std::mem::replace(&mut self.pending, io::Cursor::new(Vec::new())).into_inner();
+            chunk.drain(..pos);
+            return Ok(Async::Ready(Some(chunk)));
+        }
+        if self.done {
+            return Ok(Async::Ready(None));
+        }
+        match self.receiver.poll() {
+            Ok(Async::Ready(Some(Ok(chunk)))) => {
+                if chunk.is_empty() {
+                    self.done = true;
+                    Ok(Async::Ready(None))
+                } else {
+                    Ok(Async::Ready(Some(chunk)))
+                }
+            }
+            Ok(Async::Ready(Some(Err(e)))) => {
+                self.done = true;
+                Err(e)
+            }
+            Ok(Async::Ready(None)) | Err(()) => {
+                self.done = true;
+                Ok(Async::Ready(None))
+            }
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+        }
+    }
+}
+
+impl Read for AsyncPullAudioOutputStreamReader {
+    /// Fails with `io::ErrorKind::WouldBlock` if no audio is available,
+    /// the current task is notified once there is.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let n = self.pending.read(buf)?;
+        if n > 0 {
+            return Ok(n);
+        }
+        match self.poll() {
+            Ok(Async::Ready(Some(chunk))) => {
+                self.pending = io::Cursor::new(chunk);
+                self.pending.read(buf)
+            }
+            Ok(Async::Ready(None)) => Ok(0),
+            Ok(Async::NotReady) => Err(io::ErrorKind::WouldBlock.into()),
+            Err(e) => Err(io::Error::new(io::ErrorKind::Other, e.to_string())),
+        }
+    }
+}
+
+impl AsyncRead for AsyncPullAudioOutputStreamReader {}
+
+#[cfg(test)]
+mod tests {
+    use tokio::runtime::current_thread::Runtime;
+
+    use super::*;
+
+    // like `into_async`, the chunks are sent from another thread
+    fn async_reader(chunks: Vec<Result<Vec<u8>, SpxError>>) -> AsyncPullAudioOutputStreamReader {
+        let (mut s, r) = channel(1);
+        let thread = thread::spawn(move || {
+            for chunk in chunks {
+                s = match s.send(chunk).wait() {
+                    Ok(s) => s,
+                    Err(_) => break,
+                };
+            }
+        });
+        AsyncPullAudioOutputStreamReader {
+            receiver: r,
+            thread,
+            pending: io::Cursor::new(Vec::new()),
+            done: false,
+        }
+    }
+
+    #[test]
+    fn async_reader_copies_audio() {
+        let reader = async_reader(vec![Ok(b"abc".to_vec()), Ok(b"defg".to_vec()), Ok(Vec::new()), Ok(b"x".to_vec())]);
+        let mut runtime = Runtime::new().unwrap();
+        let (copied, _, audio) = runtime.block_on(tokio::io::copy(reader, io::Cursor::new(Vec::new()))).unwrap();
+        assert_eq!((copied, audio.into_inner()), (7, b"abcdefg".to_vec()));
+    }
+
+    #[test]
+    fn async_reader_mixes_reads_and_chunks() {
+        let reader = async_reader(vec![Ok(b"abcd".to_vec()), Ok(b"ef".to_vec()), Ok(Vec::new())]);
+        let mut runtime = Runtime::new().unwrap();
+        let (reader, head) = runtime.block_on(tokio::io::read_exact(reader, [0u8; 3])).unwrap();
+        assert_eq!(&head, b"abc");
+        let rest = runtime.block_on(reader.collect()).unwrap();
+        assert_eq!(rest, vec![b"d".to_vec(), b"ef".to_vec()]);
+    }
+
+    #[test]
+    fn async_reader_fails_on_read_errors() {
+        let reader = async_reader(vec![Ok(b"ab".to_vec()), Err(SpxError::StreamDropped)]);
+        let mut runtime = Runtime::new().unwrap();
+        match runtime.block_on(tokio::io::read_to_end(reader, Vec::new())) {
+            Err(e) => assert_eq!(e.kind(), io::ErrorKind::Other),
+            Ok((_, audio)) => panic!("expected an error, got {:?}", audio),
+        }
+    }
+
+    #[test]
+    fn async_reader_ends_when_the_thread_ends() {
+        let reader = async_reader(vec![Ok(b"ab".to_vec())]);
+        let mut runtime = Runtime::new().unwrap();
+        let (reader, audio) = runtime.block_on(tokio::io::read_to_end(reader, Vec::new())).unwrap();
+        assert_eq!(audio, b"ab".to_vec());
+        reader.join().unwrap();
+    }
+}
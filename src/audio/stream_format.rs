@@ -4,6 +4,15 @@ use crate::speech_api::*;
 use crate::SpxError;
 use crate::SPXHANDLE_INVALID;
 
+/// Compressed audio formats, decoding requires GStreamer to be installed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerFormat {
+    OggOpus = 0x101,
+    Mp3 = 0x102,
+    /// Not supported by the Speech SDK yet.
+    Flac = 0x103,
+}
+
 #[derive(Debug)]
 pub struct AudioStreamFormat {
     handle: SmartHandle<SPXAUDIOSTREAMFORMATHANDLE>,
@@ -21,6 +30,17 @@ impl AudioStreamFormat {
         Ok(result)
     }
 
+    pub fn get_default_output_format() -> Result<AudioStreamFormat, SpxError> {
+        let mut handle = SPXHANDLE_INVALID;
+        unsafe {
+            convert_err(audio_stream_format_create_from_default_output(&mut handle))?;
+        }
+        let result = AudioStreamFormat {
+            handle: SmartHandle::create("AudioStreamFormat", handle, audio_stream_format_release),
+        };
+        Ok(result)
+    }
+
     pub fn get_wave_format_pcm(samples_per_second: u32, bits_per_sample: Option<u8>, channels: Option<u8>) -> Result<AudioStreamFormat, SpxError> {
         let mut handle = SPXHANDLE_INVALID;
         unsafe {
@@ -37,6 +57,30 @@ impl AudioStreamFormat {
         Ok(result)
     }
 
+    /// Input format for compressed audio, usable with push and pull input streams.
+    /// Fails with `SpxError::GstreamerNotFound` or `SpxError::ContainerFormatNotSupported`,
+    /// depending on the platform either here or when recognition starts.
+    pub fn compressed(format: ContainerFormat) -> Result<AudioStreamFormat, SpxError> {
+        let mut handle = SPXHANDLE_INVALID;
+        unsafe {
+            convert_err(audio_stream_format_create_from_compressed_format(
+                &mut handle,
+                format as Audio_Stream_Container_Format,
+            ))?;
+        }
+        let result = AudioStreamFormat {
+            handle: SmartHandle::create("AudioStreamFormat", handle, audio_stream_format_release),
+        };
+        Ok(result)
+    }
+
+    pub(crate)
+    fn from_handle(handle: SPXAUDIOSTREAMFORMATHANDLE) -> AudioStreamFormat {
+        AudioStreamFormat {
+            handle: SmartHandle::create("AudioStreamFormat", handle, audio_stream_format_release),
+        }
+    }
+
     #[inline]
     pub fn get_handle(&self) -> SPXAUDIOSTREAMFORMATHANDLE {
         self.handle.get()
@@ -0,0 +1,537 @@
+use std::io;
+use std::io::Read;
+
+use crate::audio::AudioStreamFormat;
+use crate::AudioCodec;
+use crate::AudioContainer;
+use crate::AudioFormatDescriptor;
+use crate::SpxError;
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+const WAVE_FORMAT_MULAW: u16 = 7;
+const WAVE_FORMAT_SIREN: u16 = 0x028e;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xfffe;
+
+/// Sub format GUIDs of `WAVE_FORMAT_EXTENSIBLE` are the format tag followed by these bytes.
+const SUBFORMAT_GUID_TAIL: [u8; 14] = [
+    0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xaa, 0x00, 0x38, 0x9b, 0x71,
+];
+
+const MAX_FMT_CHUNK_SIZE: u32 = 1024;
+
+/// Bits per sample of the audio passed to the Speech SDK, other sample encodings are converted.
+const OUTPUT_BITS_PER_SAMPLE: u16 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SampleEncoding {
+    /// Integer samples, unsigned for 8 bit, signed otherwise.
+    Int,
+    /// IEEE float samples.
+    Float,
+}
+
+/// Sample format from the `fmt ` chunk of a wave file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WaveFormat {
+    pub encoding: SampleEncoding,
+    pub sample_rate: u32,
+    pub bits_per_sample: u16,
+    pub channels: u16,
+}
+
+impl WaveFormat {
+    /// Format of the audio after conversion, always 16 bit PCM.
+    pub fn to_stream_format(&self) -> Result<AudioStreamFormat, SpxError> {
+        AudioStreamFormat::get_wave_format_pcm(
+            self.sample_rate,
+            Some(OUTPUT_BITS_PER_SAMPLE as u8),
+            Some(self.channels as u8),
+        )
+    }
+
+    /// Whether samples have to be converted before they are passed to the Speech SDK.
+    pub fn needs_conversion(&self) -> bool {
+        self.encoding != SampleEncoding::Int || self.bits_per_sample != OUTPUT_BITS_PER_SAMPLE
+    }
+
+    #[inline]
+    pub(crate)
+    fn bytes_per_sample(&self) -> usize {
+        self.bits_per_sample as usize / 8
+    }
+}
+
+/// Reads the header up to the start of the samples.
+/// Returns the format and the length of the `data` chunk, `None` if the length is unknown (streamed wave).
+pub(crate)
+fn read_header<R: Read>(reader: &mut R) -> Result<(WaveFormat, Option<u32>), SpxError> {
+    let riff_size = read_riff_header(reader)?;
+    let mut format = None;
+    loop {
+        let mut chunk = [0u8; 8];
+        read_exact(reader, &mut chunk, if format.is_none() { "missing fmt chunk" } else { "missing data chunk" })?;
+        let size = u32_at(&chunk, 4);
+        match &chunk[0..4] {
+            b"fmt " => {
+                format = Some(read_fmt_chunk(reader, size)?.wave_format()?);
+            }
+            b"data" => {
+                let format = format.ok_or_else(|| invalid("data chunk before fmt chunk"))?;
+                // streaming writers leave both sizes at 0 or u32::MAX, otherwise an empty data chunk is empty
+                let streamed = matches!(riff_size, 0 | u32::MAX);
+                let len = if size == u32::MAX || (size == 0 && streamed) { None } else { Some(size) };
+                return Ok((format, len));
+            }
+            // LIST, fact, cue etc.
+            _ => {
+                skip(reader, u64::from(size) + u64::from(size & 1))?;
+            }
+        }
+    }
+}
+
+/// Reads the header up to the `fmt ` chunk, unlike `read_header` any codec is accepted.
+pub(crate)
+fn read_fmt<R: Read>(reader: &mut R) -> Result<FmtChunk, SpxError> {
+    read_riff_header(reader)?;
+    loop {
+        let mut chunk = [0u8; 8];
+        read_exact(reader, &mut chunk, "missing fmt chunk")?;
+        let size = u32_at(&chunk, 4);
+        match &chunk[0..4] {
+            b"fmt " => return read_fmt_chunk(reader, size),
+            b"data" => return Err(invalid("data chunk before fmt chunk")),
+            _ => skip(reader, u64::from(size) + u64::from(size & 1))?,
+        }
+    }
+}
+
+/// Returns the size of the RIFF chunk.
+fn read_riff_header<R: Read>(reader: &mut R) -> Result<u32, SpxError> {
+    let mut riff = [0u8; 12];
+    read_exact(reader, &mut riff, "unexpected end of RIFF header")?;
+    if &riff[0..4] != b"RIFF" || &riff[8..12] != b"WAVE" {
+        return Err(invalid("not a RIFF/WAVE file"));
+    }
+    Ok(u32_at(&riff, 4))
+}
+
+/// Fields of the `fmt ` chunk, `WAVE_FORMAT_EXTENSIBLE` is replaced by its sub format.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate)
+struct FmtChunk {
+    format_tag: u16,
+    channels: u16,
+    sample_rate: u32,
+    avg_bytes_per_sec: u32,
+    block_align: u16,
+    bits_per_sample: u16,
+}
+
+fn read_fmt_chunk<R: Read>(reader: &mut R, size: u32) -> Result<FmtChunk, SpxError> {
+    if size < 16 {
+        return Err(invalid(format!("fmt chunk is too short ({} bytes)", size)));
+    }
+    if size > MAX_FMT_CHUNK_SIZE {
+        return Err(invalid(format!("fmt chunk is too long ({} bytes)", size)));
+    }
+    let mut fmt = vec![0u8; size as usize];
+    read_exact(reader, &mut fmt, "unexpected end of fmt chunk")?;
+    skip_padding(reader, size)?;
+    parse_fmt(&fmt)
+}
+
+fn parse_fmt(fmt: &[u8]) -> Result<FmtChunk, SpxError> {
+    let mut format_tag = u16_at(fmt, 0);
+    if format_tag == WAVE_FORMAT_EXTENSIBLE {
+        if fmt.len() < 40 || u16_at(fmt, 16) < 22 {
+            return Err(invalid("fmt chunk of WAVE_FORMAT_EXTENSIBLE is too short"));
+        }
+        if fmt[26..40] != SUBFORMAT_GUID_TAIL {
+            return Err(invalid("unknown sub format of WAVE_FORMAT_EXTENSIBLE"));
+        }
+        format_tag = u16_at(fmt, 24);
+    }
+    Ok(FmtChunk {
+        format_tag,
+        channels: u16_at(fmt, 2),
+        sample_rate: u32_at(fmt, 4),
+        avg_bytes_per_sec: u32_at(fmt, 8),
+        block_align: u16_at(fmt, 12),
+        bits_per_sample: u16_at(fmt, 14),
+    })
+}
+
+impl FmtChunk {
+    /// Describes synthesized RIFF audio, fails for codecs the Speech SDK does not synthesize.
+    pub(crate)
+    fn descriptor(&self) -> Result<AudioFormatDescriptor, SpxError> {
+        let codec = match self.format_tag {
+            WAVE_FORMAT_PCM => AudioCodec::Pcm,
+            WAVE_FORMAT_MULAW => AudioCodec::MuLaw,
+            WAVE_FORMAT_SIREN => AudioCodec::Siren,
+            x => return Err(invalid(format!("unknown format tag 0x{:04x} of synthesized audio", x))),
+        };
+        let bits_per_sample = match codec {
+            AudioCodec::Pcm | AudioCodec::MuLaw => Some(self.bits_per_sample as u8),
+            _ => None,
+        };
+        Ok(AudioFormatDescriptor {
+            sample_rate: self.sample_rate,
+            bits_per_sample,
+            channels: self.channels as u8,
+            container: AudioContainer::Riff,
+            codec,
+            bit_rate: Some(self.avg_bytes_per_sec * 8),
+        })
+    }
+
+    /// Format of input audio, only PCM and IEEE float samples are supported.
+    fn wave_format(&self) -> Result<WaveFormat, SpxError> {
+        let FmtChunk { format_tag, channels, sample_rate, block_align, bits_per_sample, .. } = *self;
+        let encoding = match format_tag {
+            WAVE_FORMAT_PCM => SampleEncoding::Int,
+            WAVE_FORMAT_IEEE_FLOAT => SampleEncoding::Float,
+            x => return Err(invalid(format!("unsupported format tag 0x{:04x}, only PCM and IEEE float are supported", x))),
+        };
+        match (encoding, bits_per_sample) {
+            (SampleEncoding::Int, 8) | (SampleEncoding::Int, 16) | (SampleEncoding::Int, 24) | (SampleEncoding::Int, 32) => {}
+            (SampleEncoding::Float, 32) | (SampleEncoding::Float, 64) => {}
+            (encoding, bits) => return Err(invalid(format!("unsupported sample format {:?} {} bit", encoding, bits))),
+        }
+        if channels == 0 || channels > u16::from(u8::MAX) {
+            return Err(invalid(format!("unsupported number of channels {}", channels)));
+        }
+        if sample_rate == 0 {
+            return Err(invalid("sample rate is 0"));
+        }
+        if u32::from(block_align) != u32::from(channels) * u32::from(bits_per_sample / 8) {
+            return Err(invalid(format!(
+                "block align {} does not match {} channels of {} bit",
+                block_align,
+                channels,
+                bits_per_sample,
+            )));
+        }
+        Ok(WaveFormat {
+            encoding,
+            sample_rate,
+            bits_per_sample,
+            channels,
+        })
+    }
+}
+
+/// A wave file without samples, `format` must be one of the RIFF synthesis output formats.
+pub(crate)
+fn empty_wave(format: &AudioFormatDescriptor) -> Vec<u8> {
+    let channels = u16::from(format.channels);
+    let bits_per_sample = u16::from(format.bits_per_sample.unwrap_or(0));
+    let (format_tag, block_align, extra): (u16, u16, &[u8]) = match format.codec {
+        AudioCodec::MuLaw => (WAVE_FORMAT_MULAW, channels, &[]),
+        // cbSize followed by 320 samples per frame
+        AudioCodec::Siren => (WAVE_FORMAT_SIREN, 40 * channels, &[2, 0, 0x40, 0x01]),
+        _ => (WAVE_FORMAT_PCM, channels * (bits_per_sample / 8), &[]),
+    };
+    let fmt_size = 16 + extra.len() as u32;
+    let mut result = Vec::with_capacity(28 + fmt_size as usize);
+    result.extend_from_slice(b"RIFF");
+    result.extend_from_slice(&(4 + 8 + fmt_size + 8).to_le_bytes());
+    result.extend_from_slice(b"WAVEfmt ");
+    result.extend_from_slice(&fmt_size.to_le_bytes());
+    result.extend_from_slice(&format_tag.to_le_bytes());
+    result.extend_from_slice(&channels.to_le_bytes());
+    result.extend_from_slice(&format.sample_rate.to_le_bytes());
+    result.extend_from_slice(&(format.bit_rate.unwrap_or(0) / 8).to_le_bytes());
+    result.extend_from_slice(&block_align.to_le_bytes());
+    result.extend_from_slice(&bits_per_sample.to_le_bytes());
+    result.extend_from_slice(extra);
+    result.extend_from_slice(b"data");
+    result.extend_from_slice(&0u32.to_le_bytes());
+    result
+}
+
+/// Converts whole samples of `src` to 16 bit PCM, `dst` must be large enough.
+/// Returns the number of bytes written.
+pub(crate)
+fn convert_samples(format: &WaveFormat, src: &[u8], dst: &mut [u8]) -> usize {
+    let mut written = 0;
+    for sample in src.chunks_exact(format.bytes_per_sample()) {
+        let value: i16 = match (format.encoding, sample.len()) {
+            (SampleEncoding::Int, 1) => (i16::from(sample[0]) - 128) << 8,
+            (SampleEncoding::Int, 2) => i16::from_le_bytes([sample[0], sample[1]]),
+            (SampleEncoding::Int, 3) => i16::from_le_bytes([sample[1], sample[2]]),
+            (SampleEncoding::Int, 4) => i16::from_le_bytes([sample[2], sample[3]]),
+            (SampleEncoding::Float, 4) => {
+                float_to_i16(f64::from(f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]])))
+            }
+            (SampleEncoding::Float, 8) => {
+                let mut bytes = [0u8; 8];
+                bytes.copy_from_slice(sample);
+                float_to_i16(f64::from_le_bytes(bytes))
+            }
+            _ => unreachable!("sample format is checked when the header is parsed"),
+        };
+        dst[written..written + 2].copy_from_slice(&value.to_le_bytes());
+        written += 2;
+    }
+    written
+}
+
+#[inline]
+fn float_to_i16(x: f64) -> i16 {
+    if x.is_nan() {
+        return 0;
+    }
+    (x.clamp(-1.0, 1.0) * f64::from(i16::MAX)).round() as i16
+}
+
+fn read_exact<R: Read>(reader: &mut R, buf: &mut [u8], eof_msg: &'static str) -> Result<(), SpxError> {
+    reader.read_exact(buf).map_err(|e| {
+        if e.kind() == io::ErrorKind::UnexpectedEof {
+            invalid(eof_msg)
+        } else {
+            SpxError::from(e)
+        }
+    })
+}
+
+fn skip<R: Read>(reader: &mut R, len: u64) -> Result<(), SpxError> {
+    let skipped = io::copy(&mut reader.take(len), &mut io::sink())?;
+    if skipped < len {
+        return Err(invalid("unexpected end of chunk"));
+    }
+    Ok(())
+}
+
+/// Chunks are word aligned.
+fn skip_padding<R: Read>(reader: &mut R, size: u32) -> Result<(), SpxError> {
+    skip(reader, u64::from(size & 1))
+}
+
+fn invalid(msg: impl Into<String>) -> SpxError {
+    SpxError::InvalidWaveFile(msg.into())
+}
+
+#[inline]
+fn u16_at(buf: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes([buf[pos], buf[pos + 1]])
+}
+
+#[inline]
+fn u32_at(buf: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([buf[pos], buf[pos + 1], buf[pos + 2], buf[pos + 3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::SpeechSynthesisOutputFormat;
+
+    use super::*;
+
+    fn fmt_chunk(format_tag: u16, channels: u16, bits_per_sample: u16) -> Vec<u8> {
+        let block_align = channels * (bits_per_sample / 8);
+        let mut fmt = Vec::new();
+        fmt.extend_from_slice(&format_tag.to_le_bytes());
+        fmt.extend_from_slice(&channels.to_le_bytes());
+        fmt.extend_from_slice(&16000u32.to_le_bytes());
+        fmt.extend_from_slice(&(16000 * u32::from(block_align)).to_le_bytes());
+        fmt.extend_from_slice(&block_align.to_le_bytes());
+        fmt.extend_from_slice(&bits_per_sample.to_le_bytes());
+        fmt
+    }
+
+    fn extensible_fmt_chunk(sub_format: u16, channels: u16, bits_per_sample: u16) -> Vec<u8> {
+        let mut fmt = fmt_chunk(WAVE_FORMAT_EXTENSIBLE, channels, bits_per_sample);
+        fmt.extend_from_slice(&22u16.to_le_bytes());
+        fmt.extend_from_slice(&bits_per_sample.to_le_bytes());
+        fmt.extend_from_slice(&0u32.to_le_bytes());
+        fmt.extend_from_slice(&sub_format.to_le_bytes());
+        fmt.extend_from_slice(&SUBFORMAT_GUID_TAIL);
+        fmt
+    }
+
+    fn chunk(id: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut result = id.to_vec();
+        result.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        result.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            result.push(0);
+        }
+        result
+    }
+
+    fn riff(riff_size: u32, chunks: &[Vec<u8>]) -> Vec<u8> {
+        let mut result = b"RIFF".to_vec();
+        result.extend_from_slice(&riff_size.to_le_bytes());
+        result.extend_from_slice(b"WAVE");
+        for chunk in chunks {
+            result.extend_from_slice(chunk);
+        }
+        result
+    }
+
+    fn wave(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let size = 4 + chunks.iter().map(|x| x.len() as u32).sum::<u32>();
+        riff(size, chunks)
+    }
+
+    fn header(bytes: &[u8]) -> Result<(WaveFormat, Option<u32>), SpxError> {
+        read_header(&mut &bytes[..])
+    }
+
+    fn error(bytes: &[u8]) -> String {
+        match header(bytes) {
+            Err(SpxError::InvalidWaveFile(msg)) => msg,
+            x => panic!("expected InvalidWaveFile, got {:?}", x),
+        }
+    }
+
+    fn format(encoding: SampleEncoding, bits_per_sample: u16) -> WaveFormat {
+        WaveFormat { encoding, sample_rate: 16000, bits_per_sample, channels: 1 }
+    }
+
+    fn convert(format: &WaveFormat, src: &[u8]) -> Vec<i16> {
+        let mut dst = vec![0u8; src.len() / format.bytes_per_sample() * 2];
+        let written = convert_samples(format, src, &mut dst);
+        assert_eq!(written, dst.len());
+        dst.chunks_exact(2).map(|x| i16::from_le_bytes([x[0], x[1]])).collect()
+    }
+
+    #[test]
+    fn reads_pcm_header() {
+        let bytes = wave(&[chunk(b"fmt ", &fmt_chunk(WAVE_FORMAT_PCM, 2, 16)), chunk(b"data", &[0; 8])]);
+        let (format, len) = header(&bytes).unwrap();
+        assert_eq!(format, WaveFormat { encoding: SampleEncoding::Int, sample_rate: 16000, bits_per_sample: 16, channels: 2 });
+        assert!(!format.needs_conversion());
+        assert_eq!(len, Some(8));
+    }
+
+    #[test]
+    fn skips_chunks_before_fmt_and_data() {
+        let bytes = wave(&[
+            chunk(b"LIST", b"odd"),
+            chunk(b"fmt ", &fmt_chunk(WAVE_FORMAT_IEEE_FLOAT, 1, 32)),
+            chunk(b"fact", &[1, 0, 0, 0]),
+            chunk(b"data", &[0; 4]),
+        ]);
+        let (wave_format, len) = header(&bytes).unwrap();
+        assert_eq!(wave_format, format(SampleEncoding::Float, 32));
+        assert!(wave_format.needs_conversion());
+        assert_eq!(len, Some(4));
+    }
+
+    #[test]
+    fn reads_extensible_header() {
+        let bytes = wave(&[chunk(b"fmt ", &extensible_fmt_chunk(WAVE_FORMAT_PCM, 1, 24)), chunk(b"data", &[])]);
+        assert_eq!(header(&bytes).unwrap(), (format(SampleEncoding::Int, 24), Some(0)));
+
+        let mut fmt = extensible_fmt_chunk(WAVE_FORMAT_PCM, 1, 24);
+        fmt[39] = 0;
+        let bytes = wave(&[chunk(b"fmt ", &fmt), chunk(b"data", &[])]);
+        assert_eq!(error(&bytes), "unknown sub format of WAVE_FORMAT_EXTENSIBLE");
+    }
+
+    #[test]
+    fn data_length_is_unknown_for_streamed_files() {
+        let fmt = chunk(b"fmt ", &fmt_chunk(WAVE_FORMAT_PCM, 1, 16));
+        let mut data = b"data".to_vec();
+        data.extend_from_slice(&0u32.to_le_bytes());
+        assert_eq!(header(&riff(0, &[fmt.clone(), data.clone()])).unwrap().1, None);
+        assert_eq!(header(&riff(u32::MAX, &[fmt.clone(), data.clone()])).unwrap().1, None);
+        assert_eq!(header(&wave(&[fmt.clone(), data])).unwrap().1, Some(0));
+
+        let mut data = b"data".to_vec();
+        data.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(header(&wave(&[fmt, data])).unwrap().1, None);
+    }
+
+    #[test]
+    fn rejects_malformed_headers() {
+        let fmt = chunk(b"fmt ", &fmt_chunk(WAVE_FORMAT_PCM, 1, 16));
+        let data = chunk(b"data", &[0; 2]);
+        assert_eq!(error(b"RIFF"), "unexpected end of RIFF header");
+        assert_eq!(error(b"RIFF\0\0\0\0AVI "), "not a RIFF/WAVE file");
+        assert_eq!(error(&wave(&[])), "missing fmt chunk");
+        assert_eq!(error(&wave(std::slice::from_ref(&fmt))), "missing data chunk");
+        assert_eq!(error(&wave(&[data.clone(), fmt.clone()])), "data chunk before fmt chunk");
+        assert_eq!(error(&wave(&[chunk(b"fmt ", &[0; 14])])), "fmt chunk is too short (14 bytes)");
+        assert_eq!(error(&wave(&[fmt[..12].to_vec()])), "unexpected end of fmt chunk");
+        assert_eq!(error(&wave(&[chunk(b"LIST", &[0; 8])[..10].to_vec()])), "unexpected end of chunk");
+        assert_eq!(error(&wave(&[chunk(b"fmt ", &fmt_chunk(2, 1, 16)), data.clone()])),
+                   "unsupported format tag 0x0002, only PCM and IEEE float are supported");
+        assert_eq!(error(&wave(&[chunk(b"fmt ", &fmt_chunk(WAVE_FORMAT_IEEE_FLOAT, 1, 16)), data.clone()])),
+                   "unsupported sample format Float 16 bit");
+        assert_eq!(error(&wave(&[chunk(b"fmt ", &fmt_chunk(WAVE_FORMAT_PCM, 0, 16)), data.clone()])),
+                   "unsupported number of channels 0");
+
+        let mut bad_align = fmt_chunk(WAVE_FORMAT_PCM, 2, 16);
+        bad_align[12] = 2;
+        assert_eq!(error(&wave(&[chunk(b"fmt ", &bad_align), data])),
+                   "block align 2 does not match 2 channels of 16 bit");
+    }
+
+    #[test]
+    fn describes_synthesized_riff_audio() {
+        let mulaw = wave(&[chunk(b"fmt ", &fmt_chunk(WAVE_FORMAT_MULAW, 1, 8)), chunk(b"data", &[])]);
+        assert_eq!(read_fmt(&mut &mulaw[..]).unwrap().descriptor().unwrap(), AudioFormatDescriptor {
+            sample_rate: 16000,
+            bits_per_sample: Some(8),
+            channels: 1,
+            container: AudioContainer::Riff,
+            codec: AudioCodec::MuLaw,
+            bit_rate: Some(128_000),
+        });
+
+        let mut siren = fmt_chunk(WAVE_FORMAT_SIREN, 1, 0);
+        siren[8..12].copy_from_slice(&2000u32.to_le_bytes());
+        let siren = wave(&[chunk(b"LIST", &[0; 4]), chunk(b"fmt ", &siren)]);
+        let descriptor = read_fmt(&mut &siren[..]).unwrap().descriptor().unwrap();
+        assert_eq!((descriptor.codec, descriptor.bits_per_sample, descriptor.bit_rate), (AudioCodec::Siren, None, Some(16_000)));
+
+        let float = wave(&[chunk(b"fmt ", &fmt_chunk(WAVE_FORMAT_IEEE_FLOAT, 1, 32))]);
+        match read_fmt(&mut &float[..]).unwrap().descriptor() {
+            Err(SpxError::InvalidWaveFile(msg)) => assert_eq!(msg, "unknown format tag 0x0003 of synthesized audio"),
+            x => panic!("expected InvalidWaveFile, got {:?}", x),
+        }
+        let data_first = wave(&[chunk(b"data", &[]), chunk(b"fmt ", &fmt_chunk(WAVE_FORMAT_PCM, 1, 16))]);
+        assert!(read_fmt(&mut &data_first[..]).is_err());
+    }
+
+    #[test]
+    fn writes_empty_wave_of_riff_output_formats() {
+        for format in SpeechSynthesisOutputFormat::ALL.iter() {
+            let descriptor = format.descriptor();
+            if descriptor.container != AudioContainer::Riff {
+                continue;
+            }
+            let wave = empty_wave(&descriptor);
+            assert_eq!(u32_at(&wave, 4) as usize, wave.len() - 8, "{}", format);
+            assert_eq!(&wave[wave.len() - 8..], b"data\0\0\0\0", "{}", format);
+            assert_eq!(read_fmt(&mut &wave[..]).unwrap().descriptor().unwrap(), descriptor, "{}", format);
+        }
+    }
+
+    #[test]
+    fn converts_int_samples() {
+        assert_eq!(convert(&format(SampleEncoding::Int, 8), &[0, 128, 255]), vec![i16::MIN, 0, 0x7f00]);
+        assert_eq!(convert(&format(SampleEncoding::Int, 16), &[0x34, 0x12, 0xff, 0xff]), vec![0x1234, -1]);
+        assert_eq!(convert(&format(SampleEncoding::Int, 24), &[0x56, 0x34, 0x12, 0x00, 0x00, 0x80]),
+                   vec![0x1234, i16::MIN]);
+        assert_eq!(convert(&format(SampleEncoding::Int, 32), &[0x78, 0x56, 0x34, 0x12, 0xff, 0xff, 0xff, 0x7f]),
+                   vec![0x1234, i16::MAX]);
+    }
+
+    #[test]
+    fn converts_and_clamps_float_samples() {
+        let samples: Vec<u8> = [0.0f32, 0.5, -1.0, 2.0, -2.0, f32::NAN].iter()
+            .flat_map(|x| x.to_le_bytes().to_vec())
+            .collect();
+        let expected = vec![0, 16384, -i16::MAX, i16::MAX, -i16::MAX, 0];
+        assert_eq!(convert(&format(SampleEncoding::Float, 32), &samples), expected);
+
+        let samples: Vec<u8> = [0.5f64, 1.5].iter().flat_map(|x| x.to_le_bytes().to_vec()).collect();
+        assert_eq!(convert(&format(SampleEncoding::Float, 64), &samples), vec![16384, i16::MAX]);
+    }
+}
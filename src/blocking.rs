@@ -0,0 +1,42 @@
+use futures::Stream;
+use futures::stream::Wait;
+use futures::sync::mpsc::Receiver;
+
+use crate::SpxError;
+
+/// Blocking variants of the `AsyncRecognizer` methods, these block the calling thread
+/// and do not need an executor.
+pub trait BlockingRecognizer<R> {
+    fn recognize_once_blocking(&mut self) -> Result<R, SpxError>;
+    fn start_continuous_recognition_blocking(&mut self) -> Result<(), SpxError>;
+    fn stop_continuous_recognition_blocking(&mut self) -> Result<(), SpxError>;
+}
+
+/// Blocking iterator over the events of a `connect_*` receiver, does not need an executor.
+/// Iteration ends when the sending side (the recognizer or synthesizer) is dropped or disconnected.
+pub struct EventIterator<T> {
+    inner: Wait<Receiver<T>>,
+}
+
+impl<T> EventIterator<T> {
+    pub fn new(receiver: Receiver<T>) -> EventIterator<T> {
+        EventIterator {
+            inner: receiver.wait(),
+        }
+    }
+}
+
+impl<T> From<Receiver<T>> for EventIterator<T> {
+    fn from(receiver: Receiver<T>) -> Self {
+        EventIterator::new(receiver)
+    }
+}
+
+impl<T> Iterator for EventIterator<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        // Receiver never fails
+        self.inner.next().and_then(|x| x.ok())
+    }
+}
@@ -0,0 +1,601 @@
+//! Disk cache of synthesized speech.
+//!
+//! Entries are addressed by a hash of voice, language, output format and normalized SSML,
+//! each one is stored as `<hash>.audio` plus a `<hash>.json` with its metadata.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use futures::future::{self, Either};
+use futures::prelude::*;
+
+use crate::ResultReason;
+use crate::SpeechConfig;
+use crate::SpeechSynthesisOutputFormat;
+use crate::SpxError;
+use crate::ssml::SsmlBuilder;
+use crate::synthesizer::SpeechSynthesizer;
+
+const AUDIO_EXT: &str = "audio";
+const META_EXT: &str = "json";
+const TMP_EXT: &str = "tmp";
+// expired entries are only found by scanning the directory, this limits how often that happens
+const MIN_SWEEP_INTERVAL_SECS: u64 = 60;
+
+// makes temporary file names unique within the process, the pid makes them unique across processes
+static TMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CacheKey {
+    pub voice: String,
+    pub language: String,
+    /// Service name of the `SpeechSynthesisOutputFormat`.
+    pub format: String,
+    pub ssml: String,
+}
+
+impl CacheKey {
+    /// Whitespace outside of attribute values is collapsed in `ssml`, so documents differing only
+    /// in layout share an entry. Documents using `xml:space="preserve"` are kept as they are.
+    pub fn new(voice: impl Into<String>,
+               language: impl Into<String>,
+               format: SpeechSynthesisOutputFormat,
+               ssml: impl AsRef<str>) -> CacheKey {
+        CacheKey {
+            voice: voice.into(),
+            language: language.into(),
+            format: format.to_string(),
+            ssml: normalize_ssml(ssml.as_ref()),
+        }
+    }
+
+    fn hash(&self) -> String {
+        // FNV-1a, stable across builds unlike `DefaultHasher`; collisions are caught by comparing the stored key
+        let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+        for part in [&self.voice, &self.language, &self.format, &self.ssml].iter() {
+            for b in part.bytes().chain(Some(0)) {
+                h ^= u64::from(b);
+                h = h.wrapping_mul(0x0000_0100_0000_01b3);
+            }
+        }
+        format!("{:016x}", h)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntryMeta {
+    key: CacheKey,
+    /// Seconds since the unix epoch.
+    created: u64,
+    size: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+#[derive(Debug)]
+pub struct SynthesisCache {
+    dir: PathBuf,
+    max_size: Option<u64>,
+    max_age: Option<Duration>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    usage: Mutex<Usage>,
+}
+
+/// Kept in memory so that `put` does not have to scan the directory,
+/// corrected by every scan as other processes may share the directory.
+#[derive(Debug, Default)]
+struct Usage {
+    size: u64,
+    entries: usize,
+    /// Seconds since the unix epoch.
+    last_sweep: u64,
+}
+
+impl SynthesisCache {
+    /// Creates `dir` if it does not exist, entries of previous runs are kept.
+    pub fn open(dir: impl AsRef<Path>) -> Result<SynthesisCache, SpxError> {
+        fs::create_dir_all(dir.as_ref())?;
+        let result = SynthesisCache {
+            dir: dir.as_ref().to_owned(),
+            max_size: None,
+            max_age: None,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            usage: Mutex::new(Usage::default()),
+        };
+        let entries = result.entries()?;
+        result.set_usage(&entries);
+        Ok(result)
+    }
+
+    /// Total size of the cached audio, the oldest entries are evicted first.
+    pub fn set_max_size(&mut self, bytes: u64) {
+        self.max_size = Some(bytes);
+    }
+
+    pub fn set_max_age(&mut self, age: Duration) {
+        self.max_age = Some(age);
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Total size of the cached audio as far as known to this instance.
+    pub fn size(&self) -> u64 {
+        self.usage.lock().unwrap().size
+    }
+
+    /// Number of entries as far as known to this instance.
+    pub fn len(&self) -> usize {
+        self.usage.lock().unwrap().entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Counts a hit or a miss, expired entries are removed and reported as a miss.
+    pub fn get(&self, key: &CacheKey) -> Result<Option<Vec<u8>>, SpxError> {
+        let result = self.lookup(key)?;
+        let counter = if result.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        Ok(result)
+    }
+
+    /// Entries are only evicted when the cache is over its size limit,
+    /// expired entries are removed at most every `max_age`.
+    pub fn put(&self, key: &CacheKey, audio: &[u8]) -> Result<(), SpxError> {
+        let hash = key.hash();
+        let meta = CacheEntryMeta {
+            key: key.clone(),
+            created: now_secs(),
+            size: audio.len() as u64,
+        };
+        let replaced = self.read_meta(&hash).ok().and_then(|x| x).map(|x| x.size);
+        // the metadata is written last, an entry without it does not exist
+        self.write_atomic(&self.path(&hash, AUDIO_EXT), audio)?;
+        self.write_atomic(&self.path(&hash, META_EXT), &serde_json::to_vec(&meta)?)?;
+        let needs_scan = {
+            let mut usage = self.usage.lock().unwrap();
+            match replaced {
+                Some(size) => usage.size = usage.size.saturating_sub(size),
+                None => usage.entries += 1,
+            }
+            usage.size += meta.size;
+            let over_size = self.max_size.map_or(false, |max_size| usage.size > max_size);
+            let sweep_due = self.max_age.map_or(false, |max_age| {
+                now_secs().saturating_sub(usage.last_sweep) >= max_age.as_secs().max(MIN_SWEEP_INTERVAL_SECS)
+            });
+            over_size || sweep_due
+        };
+        if needs_scan {
+            self.evict()?;
+        }
+        Ok(())
+    }
+
+    /// Removes all entries, counters are not reset.
+    pub fn clear(&self) -> Result<(), SpxError> {
+        for (hash, _) in self.entries()? {
+            self.remove(&hash)?;
+        }
+        self.set_usage(&[]);
+        Ok(())
+    }
+
+    fn lookup(&self, key: &CacheKey) -> Result<Option<Vec<u8>>, SpxError> {
+        let hash = key.hash();
+        let meta = match self.read_meta(&hash) {
+            Ok(Some(meta)) => meta,
+            Ok(None) => return Ok(None),
+            Err(e) => {
+                warn!("removing broken cache entry {}, err: {}", hash, e);
+                self.remove(&hash)?;
+                return Ok(None);
+            }
+        };
+        if meta.key != *key {
+            return Ok(None);
+        }
+        if self.is_expired(&meta) {
+            self.remove(&hash)?;
+            self.forget(meta.size);
+            return Ok(None);
+        }
+        match fs::read(self.path(&hash, AUDIO_EXT)) {
+            Ok(audio) if audio.len() as u64 == meta.size => Ok(Some(audio)),
+            // partially written or removed by another process
+            Ok(_) => Ok(None),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn evict(&self) -> Result<(), SpxError> {
+        let mut entries = self.entries()?;
+        let mut kept = Vec::with_capacity(entries.len());
+        for (hash, meta) in entries.drain(..) {
+            if self.is_expired(&meta) {
+                self.remove(&hash)?;
+            } else {
+                kept.push((hash, meta));
+            }
+        }
+        if let Some(max_size) = self.max_size {
+            kept.sort_by_key(|(_, meta)| meta.created);
+            let mut total: u64 = kept.iter().map(|(_, meta)| meta.size).sum();
+            let mut evicted = 0;
+            for (hash, meta) in kept.iter() {
+                if total <= max_size {
+                    break;
+                }
+                self.remove(hash)?;
+                total -= meta.size;
+                evicted += 1;
+            }
+            kept.drain(..evicted);
+        }
+        self.set_usage(&kept);
+        Ok(())
+    }
+
+    fn set_usage(&self, entries: &[(String, CacheEntryMeta)]) {
+        let mut usage = self.usage.lock().unwrap();
+        usage.size = entries.iter().map(|(_, meta)| meta.size).sum();
+        usage.entries = entries.len();
+        usage.last_sweep = now_secs();
+    }
+
+    fn forget(&self, size: u64) {
+        let mut usage = self.usage.lock().unwrap();
+        usage.size = usage.size.saturating_sub(size);
+        usage.entries = usage.entries.saturating_sub(1);
+    }
+
+    fn entries(&self) -> Result<Vec<(String, CacheEntryMeta)>, SpxError> {
+        let mut result = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().map_or(true, |x| x != META_EXT) {
+                continue;
+            }
+            let hash = match path.file_stem().and_then(|x| x.to_str()) {
+                Some(x) => x.to_owned(),
+                None => continue,
+            };
+            match self.read_meta(&hash) {
+                Ok(Some(meta)) => result.push((hash, meta)),
+                Ok(None) => {}
+                Err(e) => {
+                    warn!("removing broken cache entry {}, err: {}", hash, e);
+                    self.remove(&hash)?;
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    fn read_meta(&self, hash: &str) -> Result<Option<CacheEntryMeta>, SpxError> {
+        match fs::read(self.path(hash, META_EXT)) {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn is_expired(&self, meta: &CacheEntryMeta) -> bool {
+        self.max_age.map_or(false, |max_age| now_secs().saturating_sub(meta.created) > max_age.as_secs())
+    }
+
+    fn remove(&self, hash: &str) -> Result<(), SpxError> {
+        for ext in [META_EXT, AUDIO_EXT].iter() {
+            match fs::remove_file(self.path(hash, ext)) {
+                Ok(()) => {}
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Concurrent writers of the same entry each use their own temporary file, the last rename wins.
+    fn write_atomic(&self, path: &Path, data: &[u8]) -> Result<(), SpxError> {
+        let file_name = path.file_name().and_then(|x| x.to_str()).unwrap_or_default();
+        let tmp = self.dir.join(format!(
+            ".{}.{}.{}.{}",
+            file_name,
+            process::id(),
+            TMP_COUNTER.fetch_add(1, Ordering::Relaxed),
+            TMP_EXT,
+        ));
+        let result = fs::write(&tmp, data).and_then(|_| fs::rename(&tmp, path));
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        Ok(result?)
+    }
+
+    #[inline]
+    fn path(&self, hash: &str, ext: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", hash, ext))
+    }
+}
+
+/// Audio of a synthesis, either from the cache or freshly synthesized.
+/// Mirrors the accessors of `SpeechSynthesisResult`.
+#[derive(Debug, Clone)]
+pub struct CachedSynthesisResult {
+    audio: Vec<u8>,
+    from_cache: bool,
+}
+
+impl CachedSynthesisResult {
+    pub fn reason(&self) -> Result<ResultReason, SpxError> {
+        Ok(ResultReason::SynthesizingAudioCompleted)
+    }
+
+    pub fn audio_length(&self) -> Result<u32, SpxError> {
+        Ok(self.audio.len() as u32)
+    }
+
+    pub fn audio_data(&self) -> Result<Vec<u8>, SpxError> {
+        Ok(self.audio.clone())
+    }
+
+    pub fn into_audio_data(self) -> Vec<u8> {
+        self.audio
+    }
+
+    pub fn is_from_cache(&self) -> bool {
+        self.from_cache
+    }
+}
+
+/// `SpeechSynthesizer` that looks up every request in a `SynthesisCache` first.
+/// Plain text is turned into SSML with the voice and language of the config,
+/// so text and equivalent SSML share cache entries.
+pub struct CachedSpeechSynthesizer {
+    synthesizer: SpeechSynthesizer,
+    cache: Arc<SynthesisCache>,
+    // empty document with the defaults of the config
+    template: SsmlBuilder,
+    format: SpeechSynthesisOutputFormat,
+}
+
+impl CachedSpeechSynthesizer {
+    pub fn from_config(config: &SpeechConfig, cache: SynthesisCache) -> Result<CachedSpeechSynthesizer, SpxError> {
+        Ok(CachedSpeechSynthesizer {
+            synthesizer: SpeechSynthesizer::from_config(config, None)?,
+            cache: Arc::new(cache),
+            template: SsmlBuilder::from_config(config)?,
+            format: config.audio_output_format()?.unwrap_or(SpeechSynthesisOutputFormat::DEFAULT),
+        })
+    }
+
+    pub fn cache(&self) -> &SynthesisCache {
+        &self.cache
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    /// Blocks until the audio has been read from the cache or synthesized.
+    pub fn speak_text(&self, text: impl AsRef<str>) -> Result<CachedSynthesisResult, SpxError> {
+        self.speak_ssml(self.text_to_ssml(text.as_ref())?)
+    }
+
+    pub fn speak_ssml(&self, ssml: impl AsRef<str>) -> Result<CachedSynthesisResult, SpxError> {
+        let key = self.key(ssml.as_ref());
+        if let Some(audio) = self.cache.get(&key)? {
+            return Ok(CachedSynthesisResult { audio, from_cache: true });
+        }
+        let result = self.synthesizer.speak_ssml(ssml)?;
+        store(&self.cache, &key, result.completed_audio_data()?)
+    }
+
+    pub fn speak_text_async(&self, text: impl AsRef<str>)
+                            -> Result<impl Future<Item=CachedSynthesisResult, Error=SpxError>, SpxError> {
+        self.speak_ssml_async(self.text_to_ssml(text.as_ref())?)
+    }
+
+    pub fn speak_ssml_async(&self, ssml: impl AsRef<str>)
+                            -> Result<impl Future<Item=CachedSynthesisResult, Error=SpxError>, SpxError> {
+        let key = self.key(ssml.as_ref());
+        if let Some(audio) = self.cache.get(&key)? {
+            return Ok(Either::A(future::ok(CachedSynthesisResult { audio, from_cache: true })));
+        }
+        let cache = self.cache.clone();
+        Ok(Either::B(self.synthesizer.speak_ssml_async(ssml)?
+            .and_then(move |result| store(&cache, &key, result.completed_audio_data()?))))
+    }
+
+    fn text_to_ssml(&self, text: &str) -> Result<String, SpxError> {
+        let mut builder = self.template.clone();
+        builder.text(text);
+        builder.build()
+    }
+
+    #[inline]
+    fn key(&self, ssml: &str) -> CacheKey {
+        CacheKey::new(self.template.default_voice_name().unwrap_or(""), self.template.language(), self.format, ssml)
+    }
+}
+
+fn store(cache: &SynthesisCache, key: &CacheKey, audio: Vec<u8>) -> Result<CachedSynthesisResult, SpxError> {
+    if let Err(e) = cache.put(key, &audio) {
+        // the audio is still usable
+        warn!("can not store synthesized audio in cache, err: {}", e);
+    }
+    Ok(CachedSynthesisResult { audio, from_cache: false })
+}
+
+fn normalize_ssml(ssml: &str) -> String {
+    if ssml.contains("xml:space") {
+        return ssml.to_owned();
+    }
+    let mut result = String::with_capacity(ssml.len());
+    let mut in_tag = false;
+    let mut quote = None;
+    let mut space = false;
+    let mut rest = ssml.trim();
+    while let Some(c) = rest.chars().next() {
+        if quote.is_none() && !in_tag && rest.starts_with("<!--") {
+            // comments are not spoken, quotes inside of them do not start attribute values
+            rest = rest[4..].find("-->").map_or("", |end| &rest[4 + end + 3..]);
+            continue;
+        }
+        rest = &rest[c.len_utf8()..];
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => {
+                result.push(c);
+                continue;
+            }
+            (None, '"') | (None, '\'') if in_tag => quote = Some(c),
+            (None, '<') => in_tag = true,
+            (None, '>') => in_tag = false,
+            (None, c) if c.is_whitespace() => {
+                space = true;
+                continue;
+            }
+            _ => {}
+        }
+        if space {
+            if !result.is_empty() {
+                result.push(' ');
+            }
+            space = false;
+        }
+        result.push(c);
+    }
+    result
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|x| x.as_secs()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_cache(name: &str) -> SynthesisCache {
+        let dir = std::env::temp_dir().join(format!("speech-cache-test-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        SynthesisCache::open(dir).unwrap()
+    }
+
+    fn key(ssml: &str) -> CacheKey {
+        CacheKey::new("voice", "en-US", SpeechSynthesisOutputFormat::DEFAULT, ssml)
+    }
+
+    // entries created within the same second can not be ordered
+    fn backdate(cache: &SynthesisCache, key: &CacheKey, secs: u64) {
+        let hash = key.hash();
+        let mut meta = cache.read_meta(&hash).unwrap().unwrap();
+        meta.created -= secs;
+        cache.write_atomic(&cache.path(&hash, META_EXT), &serde_json::to_vec(&meta).unwrap()).unwrap();
+    }
+
+    fn file_names(cache: &SynthesisCache) -> Vec<String> {
+        let mut result: Vec<_> = fs::read_dir(&cache.dir).unwrap()
+            .map(|x| x.unwrap().file_name().into_string().unwrap())
+            .collect();
+        result.sort();
+        result
+    }
+
+    #[test]
+    fn normalizes_whitespace_outside_of_attribute_values() {
+        assert_eq!(normalize_ssml(" <speak>\n  a  <phoneme ph=\"a  b\">x</phoneme>\t</speak> "),
+                   "<speak> a <phoneme ph=\"a  b\">x</phoneme> </speak>");
+        let preserved = "<speak xml:space=\"preserve\">a  b</speak>";
+        assert_eq!(normalize_ssml(preserved), preserved);
+        assert_eq!(key("<speak>a b</speak>"), key("<speak>a\n  b</speak>"));
+    }
+
+    #[test]
+    fn normalizes_ssml_with_comments_and_apostrophes() {
+        assert_eq!(normalize_ssml("<!-- don't  cache --> <speak>a  <!-- b's -->b\n<voice name='x  y'>it's  c</voice></speak>"),
+                   "<speak>a b <voice name='x  y'>it's c</voice></speak>");
+        assert_eq!(normalize_ssml("<speak>a<!-- -->b</speak>"), "<speak>ab</speak>");
+        assert_eq!(normalize_ssml("<speak>a  b<!-- unterminated  '"), "<speak>a b");
+        assert_eq!(key("<speak>a  b</speak> <!-- isn't spoken -->"), key("<speak>a b</speak>"));
+    }
+
+    #[test]
+    fn puts_and_gets_entries() {
+        let cache = temp_cache("round-trip");
+        let key = key("<speak>a</speak>");
+        assert_eq!(cache.get(&key).unwrap(), None);
+        cache.put(&key, b"audio").unwrap();
+        assert_eq!(cache.get(&key).unwrap(), Some(b"audio".to_vec()));
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 1 });
+        assert_eq!((cache.len(), cache.size()), (1, 5));
+
+        cache.put(&key, b"other").unwrap();
+        assert_eq!(cache.get(&key).unwrap(), Some(b"other".to_vec()));
+        assert_eq!((cache.len(), cache.size()), (1, 5));
+        // no temporary files are left behind
+        assert_eq!(file_names(&cache).len(), 2);
+
+        let reopened = SynthesisCache::open(&cache.dir).unwrap();
+        assert_eq!((reopened.len(), reopened.size()), (1, 5));
+        reopened.clear().unwrap();
+        assert!(reopened.is_empty());
+        assert_eq!(cache.get(&key).unwrap(), None);
+        fs::remove_dir_all(&cache.dir).unwrap();
+    }
+
+    #[test]
+    fn expires_entries() {
+        let mut cache = temp_cache("expiry");
+        cache.set_max_age(Duration::from_secs(60));
+        let old = key("<speak>old</speak>");
+        let new = key("<speak>new</speak>");
+        cache.put(&old, b"old").unwrap();
+        cache.put(&new, b"new").unwrap();
+        backdate(&cache, &old, 120);
+        assert_eq!(cache.get(&old).unwrap(), None);
+        assert_eq!(cache.get(&new).unwrap(), Some(b"new".to_vec()));
+        assert_eq!((cache.len(), cache.size()), (1, 3));
+        assert_eq!(file_names(&cache).len(), 2);
+        fs::remove_dir_all(&cache.dir).unwrap();
+    }
+
+    #[test]
+    fn evicts_oldest_entries_over_max_size() {
+        let mut cache = temp_cache("eviction");
+        cache.set_max_size(10);
+        let first = key("<speak>first</speak>");
+        let second = key("<speak>second</speak>");
+        let third = key("<speak>third</speak>");
+        cache.put(&first, b"1111").unwrap();
+        cache.put(&second, b"2222").unwrap();
+        backdate(&cache, &first, 2);
+        backdate(&cache, &second, 1);
+        cache.put(&third, b"3333").unwrap();
+        assert_eq!(cache.get(&first).unwrap(), None);
+        assert_eq!(cache.get(&second).unwrap(), Some(b"2222".to_vec()));
+        assert_eq!(cache.get(&third).unwrap(), Some(b"3333".to_vec()));
+        assert_eq!((cache.len(), cache.size()), (2, 8));
+        fs::remove_dir_all(&cache.dir).unwrap();
+    }
+}
@@ -1,14 +1,20 @@
+use std::borrow::Borrow;
 use std::ffi::CString;
+use std::fmt;
 use std::ops::Deref;
 use std::ops::DerefMut;
+use std::str::FromStr;
 
 use crate::convert_err;
 use crate::PropertyBag;
+use crate::PropertyId;
 use crate::SmartHandle;
 use crate::speech_api::*;
 use crate::SpxError;
 use crate::SPXHANDLE_INVALID;
 
+use self::SpeechSynthesisOutputFormat::*;
+
 #[derive(Debug)]
 pub struct SpeechConfig {
     handle: SmartHandle<SPXSPEECHCONFIGHANDLE>,
@@ -59,6 +65,14 @@ impl SpeechConfig {
             convert_err(speech_config_set_audio_output_format(self.get_handle(), format as u32))
         }
     }
+
+    /// `None` if the default output format, `SpeechSynthesisOutputFormat::DEFAULT`, is used.
+    pub fn audio_output_format(&self) -> Result<Option<SpeechSynthesisOutputFormat>, SpxError> {
+        self.get(PropertyId::SpeechServiceConnectionSynthOutputFormat)?
+            .filter(|x| !x.is_empty())
+            .map(|x| x.parse())
+            .transpose()
+    }
 }
 
 impl Deref for SpeechConfig {
@@ -75,6 +89,128 @@ impl DerefMut for SpeechConfig {
     }
 }
 
+const TRANSLATION_FEATURE_TEXT_TO_SPEECH: &str = "textToSpeech";
+
+/// `SpeechConfig` with typed access to the translation related properties.
+#[derive(Debug)]
+pub struct SpeechTranslationConfig {
+    base: SpeechConfig,
+}
+
+impl SpeechTranslationConfig {
+    pub fn from_subscription<S1, S2>(subscription: S1, region: S2) -> Result<SpeechTranslationConfig, SpxError>
+        where S1: Into<Vec<u8>>, S2: Into<Vec<u8>> {
+        Ok(SpeechTranslationConfig {
+            base: SpeechConfig::from_subscription(subscription, region)?,
+        })
+    }
+
+    pub fn from_endpoint<S1, S2>(endpoint: S1, subscription: S2) -> Result<SpeechTranslationConfig, SpxError>
+        where S1: Into<Vec<u8>>, S2: Into<Vec<u8>> {
+        Ok(SpeechTranslationConfig {
+            base: SpeechConfig::from_endpoint(endpoint, subscription)?,
+        })
+    }
+
+    /// Adds a target language, does nothing if it is already present.
+    pub fn add_target_language(&mut self, language: impl AsRef<str>) -> Result<(), SpxError> {
+        let language = language.as_ref().trim();
+        let mut languages = self.target_languages()?;
+        if !languages.iter().any(|x| x == language) {
+            languages.push(language.to_owned());
+            self.set_target_languages(&languages)?;
+        }
+        Ok(())
+    }
+
+    /// Removes a target language, does nothing if it is not present.
+    pub fn remove_target_language(&mut self, language: impl AsRef<str>) -> Result<(), SpxError> {
+        let language = language.as_ref().trim();
+        let mut languages = self.target_languages()?;
+        let len = languages.len();
+        languages.retain(|x| x != language);
+        if languages.len() != len {
+            self.set_target_languages(&languages)?;
+        }
+        Ok(())
+    }
+
+    pub fn target_languages(&self) -> Result<Vec<String>, SpxError> {
+        self.get_list(PropertyId::SpeechServiceConnectionTranslationToLanguages)
+    }
+
+    /// Sets the voice of the synthesized translation, this also enables the text to speech feature.
+    pub fn set_voice_name(&mut self, voice: impl AsRef<str>) -> Result<(), SpxError> {
+        let mut features = self.features()?;
+        if !features.iter().any(|x| x == TRANSLATION_FEATURE_TEXT_TO_SPEECH) {
+            features.push(TRANSLATION_FEATURE_TEXT_TO_SPEECH.to_owned());
+            self.set_features(features)?;
+        }
+        self.set(PropertyId::SpeechServiceConnectionTranslationVoice, voice)
+    }
+
+    pub fn voice_name(&self) -> Result<Option<String>, SpxError> {
+        Ok(self.get(PropertyId::SpeechServiceConnectionTranslationVoice)?.filter(|x| !x.is_empty()))
+    }
+
+    pub fn set_features<I, S>(&mut self, features: I) -> Result<(), SpxError>
+        where I: IntoIterator<Item=S>, S: AsRef<str> {
+        self.set_list(PropertyId::SpeechServiceConnectionTranslationFeatures, features)
+    }
+
+    pub fn features(&self) -> Result<Vec<String>, SpxError> {
+        self.get_list(PropertyId::SpeechServiceConnectionTranslationFeatures)
+    }
+
+    #[inline]
+    fn set_target_languages(&mut self, languages: &[String]) -> Result<(), SpxError> {
+        self.set_list(PropertyId::SpeechServiceConnectionTranslationToLanguages, languages)
+    }
+
+    fn get_list(&self, id: PropertyId) -> Result<Vec<String>, SpxError> {
+        Ok(self.get(id)?
+            .map(|v| {
+                v.split(',')
+                    .map(|x| x.trim())
+                    .filter(|x| !x.is_empty())
+                    .map(|x| x.to_owned())
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    fn set_list<I, S>(&mut self, id: PropertyId, values: I) -> Result<(), SpxError>
+        where I: IntoIterator<Item=S>, S: AsRef<str> {
+        let joined = values.into_iter()
+            .map(|x| x.as_ref().trim().to_owned())
+            .filter(|x| !x.is_empty())
+            .collect::<Vec<_>>()
+            .join(",");
+        self.set(id, joined)
+    }
+}
+
+impl Deref for SpeechTranslationConfig {
+    type Target = SpeechConfig;
+
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl DerefMut for SpeechTranslationConfig {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.base
+    }
+}
+
+impl Borrow<SpeechConfig> for SpeechTranslationConfig {
+    fn borrow(&self) -> &SpeechConfig {
+        &self.base
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum SpeechSynthesisOutputFormat {
     // raw-8khz-8bit-mono-mulaw
     Raw8Khz8BitMonoMULaw = 1,
@@ -127,3 +263,194 @@ pub enum SpeechSynthesisOutputFormat {
     // raw-8khz-16bit-mono-pcm
     Raw8Khz16BitMonoPcm = 17,
 }
+
+impl SpeechSynthesisOutputFormat {
+    /// Used by the Speech SDK if no output format is set.
+    pub const DEFAULT: SpeechSynthesisOutputFormat = SpeechSynthesisOutputFormat::Riff16Khz16BitMonoPcm;
+
+    pub const ALL: [SpeechSynthesisOutputFormat; 17] = [
+        Raw8Khz8BitMonoMULaw,
+        Riff16Khz16KbpsMonoSiren,
+        Audio16Khz16KbpsMonoSiren,
+        Audio16Khz32KBitRateMonoMp3,
+        Audio16Khz128KBitRateMonoMp3,
+        Audio16Khz64KBitRateMonoMp3,
+        Audio24Khz48KBitRateMonoMp3,
+        Audio24Khz96KBitRateMonoMp3,
+        Audio24Khz160KBitRateMonoMp3,
+        Raw16Khz16BitMonoTrueSilk,
+        Riff16Khz16BitMonoPcm,
+        Riff8Khz16BitMonoPcm,
+        Riff24Khz16BitMonoPcm,
+        Riff8Khz8BitMonoMULaw,
+        Raw16Khz16BitMonoPcm,
+        Raw24Khz16BitMonoPcm,
+        Raw8Khz16BitMonoPcm,
+    ];
+
+    /// Name of the format used by the service, e.g. `riff-16khz-16bit-mono-pcm`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Raw8Khz8BitMonoMULaw => "raw-8khz-8bit-mono-mulaw",
+            Riff16Khz16KbpsMonoSiren => "riff-16khz-16kbps-mono-siren",
+            Audio16Khz16KbpsMonoSiren => "audio-16khz-16kbps-mono-siren",
+            Audio16Khz32KBitRateMonoMp3 => "audio-16khz-32kbitrate-mono-mp3",
+            Audio16Khz128KBitRateMonoMp3 => "audio-16khz-128kbitrate-mono-mp3",
+            Audio16Khz64KBitRateMonoMp3 => "audio-16khz-64kbitrate-mono-mp3",
+            Audio24Khz48KBitRateMonoMp3 => "audio-24khz-48kbitrate-mono-mp3",
+            Audio24Khz96KBitRateMonoMp3 => "audio-24khz-96kbitrate-mono-mp3",
+            Audio24Khz160KBitRateMonoMp3 => "audio-24khz-160kbitrate-mono-mp3",
+            Raw16Khz16BitMonoTrueSilk => "raw-16khz-16bit-mono-truesilk",
+            Riff16Khz16BitMonoPcm => "riff-16khz-16bit-mono-pcm",
+            Riff8Khz16BitMonoPcm => "riff-8khz-16bit-mono-pcm",
+            Riff24Khz16BitMonoPcm => "riff-24khz-16bit-mono-pcm",
+            Riff8Khz8BitMonoMULaw => "riff-8khz-8bit-mono-mulaw",
+            Raw16Khz16BitMonoPcm => "raw-16khz-16bit-mono-pcm",
+            Raw24Khz16BitMonoPcm => "raw-24khz-16bit-mono-pcm",
+            Raw8Khz16BitMonoPcm => "raw-8khz-16bit-mono-pcm",
+        }
+    }
+
+    pub fn descriptor(self) -> AudioFormatDescriptor {
+        use self::AudioCodec::*;
+        use self::AudioContainer::*;
+        let (sample_rate, bits_per_sample, container, codec, bit_rate) = match self {
+            Raw8Khz8BitMonoMULaw => (8000, Some(8), Raw, MuLaw, Some(64_000)),
+            Riff16Khz16KbpsMonoSiren => (16000, None, Riff, Siren, Some(16_000)),
+            Audio16Khz16KbpsMonoSiren => (16000, None, Raw, Siren, Some(16_000)),
+            Audio16Khz32KBitRateMonoMp3 => (16000, None, Raw, Mp3, Some(32_000)),
+            Audio16Khz128KBitRateMonoMp3 => (16000, None, Raw, Mp3, Some(128_000)),
+            Audio16Khz64KBitRateMonoMp3 => (16000, None, Raw, Mp3, Some(64_000)),
+            Audio24Khz48KBitRateMonoMp3 => (24000, None, Raw, Mp3, Some(48_000)),
+            Audio24Khz96KBitRateMonoMp3 => (24000, None, Raw, Mp3, Some(96_000)),
+            Audio24Khz160KBitRateMonoMp3 => (24000, None, Raw, Mp3, Some(160_000)),
+            Raw16Khz16BitMonoTrueSilk => (16000, None, Raw, TrueSilk, None),
+            Riff16Khz16BitMonoPcm => (16000, Some(16), Riff, Pcm, Some(256_000)),
+            Riff8Khz16BitMonoPcm => (8000, Some(16), Riff, Pcm, Some(128_000)),
+            Riff24Khz16BitMonoPcm => (24000, Some(16), Riff, Pcm, Some(384_000)),
+            Riff8Khz8BitMonoMULaw => (8000, Some(8), Riff, MuLaw, Some(64_000)),
+            Raw16Khz16BitMonoPcm => (16000, Some(16), Raw, Pcm, Some(256_000)),
+            Raw24Khz16BitMonoPcm => (24000, Some(16), Raw, Pcm, Some(384_000)),
+            Raw8Khz16BitMonoPcm => (8000, Some(16), Raw, Pcm, Some(128_000)),
+        };
+        AudioFormatDescriptor {
+            sample_rate,
+            bits_per_sample,
+            channels: 1,
+            container,
+            codec,
+            bit_rate,
+        }
+    }
+}
+
+impl fmt::Display for SpeechSynthesisOutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for SpeechSynthesisOutputFormat {
+    type Err = SpxError;
+
+    /// Parses the service name of a format, case insensitive.
+    fn from_str(s: &str) -> Result<SpeechSynthesisOutputFormat, SpxError> {
+        let s = s.trim();
+        SpeechSynthesisOutputFormat::ALL.iter()
+            .find(|x| x.name().eq_ignore_ascii_case(s))
+            .cloned()
+            .ok_or_else(|| SpxError::UnknownOutputFormat(s.to_owned()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AudioContainer {
+    /// Bare audio data without any header.
+    Raw,
+    /// The audio data is preceded by a RIFF (wave) header.
+    Riff,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AudioCodec {
+    Pcm,
+    MuLaw,
+    Siren,
+    Mp3,
+    TrueSilk,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AudioFormatDescriptor {
+    pub sample_rate: u32,
+    /// Only set for sample based codecs, i.e. PCM and mu-law.
+    pub bits_per_sample: Option<u8>,
+    pub channels: u8,
+    pub container: AudioContainer,
+    pub codec: AudioCodec,
+    /// Bits per second, `None` for variable bit rate codecs.
+    pub bit_rate: Option<u32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn output_format_names_round_trip() {
+        let names: HashSet<_> = SpeechSynthesisOutputFormat::ALL.iter().map(|x| x.name()).collect();
+        assert_eq!(names.len(), SpeechSynthesisOutputFormat::ALL.len());
+        for &format in SpeechSynthesisOutputFormat::ALL.iter() {
+            let name = format.to_string();
+            assert_eq!(name, format.name());
+            assert_eq!(name.parse::<SpeechSynthesisOutputFormat>().unwrap(), format);
+            assert_eq!(name.to_uppercase().parse::<SpeechSynthesisOutputFormat>().unwrap(), format);
+        }
+        assert_eq!(" riff-16khz-16bit-mono-pcm\n".parse::<SpeechSynthesisOutputFormat>().unwrap(), SpeechSynthesisOutputFormat::DEFAULT);
+    }
+
+    #[test]
+    fn rejects_unknown_output_format_names() {
+        for name in &["", "riff-16khz-16bit-mono", "riff-16khz-16bit-mono-pcm-x", "Riff16Khz16BitMonoPcm"] {
+            match name.parse::<SpeechSynthesisOutputFormat>() {
+                Err(SpxError::UnknownOutputFormat(x)) => assert_eq!(x, name.trim()),
+                x => panic!("expected UnknownOutputFormat for {:?}, got {:?}", name, x),
+            }
+        }
+    }
+
+    #[test]
+    fn describes_output_formats() {
+        assert_eq!(Riff24Khz16BitMonoPcm.descriptor(), AudioFormatDescriptor {
+            sample_rate: 24000,
+            bits_per_sample: Some(16),
+            channels: 1,
+            container: AudioContainer::Riff,
+            codec: AudioCodec::Pcm,
+            bit_rate: Some(384_000),
+        });
+        assert_eq!(Raw8Khz8BitMonoMULaw.descriptor(), AudioFormatDescriptor {
+            sample_rate: 8000,
+            bits_per_sample: Some(8),
+            channels: 1,
+            container: AudioContainer::Raw,
+            codec: AudioCodec::MuLaw,
+            bit_rate: Some(64_000),
+        });
+        assert_eq!(Audio24Khz96KBitRateMonoMp3.descriptor(), AudioFormatDescriptor {
+            sample_rate: 24000,
+            bits_per_sample: None,
+            channels: 1,
+            container: AudioContainer::Raw,
+            codec: AudioCodec::Mp3,
+            bit_rate: Some(96_000),
+        });
+        for &format in SpeechSynthesisOutputFormat::ALL.iter() {
+            let descriptor = format.descriptor();
+            assert_eq!(descriptor.container == AudioContainer::Riff, format.name().starts_with("riff-"), "{}", format);
+            assert!(format.name().contains(&format!("{}khz", descriptor.sample_rate / 1000)), "{}", format);
+        }
+    }
+}
@@ -7,6 +7,9 @@ extern crate futures;
 #[macro_use]
 extern crate log;
 extern crate num;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
 extern crate tokio;
 
 use std::ffi;
@@ -17,21 +20,40 @@ use std::os::raw::c_char;
 
 pub use crate::async_handle::AsyncHandle;
 pub use crate::async_handle::AsyncResultHandle;
-pub use crate::config::{SpeechConfig, SpeechSynthesisOutputFormat};
+pub use crate::blocking::BlockingRecognizer;
+pub use crate::blocking::EventIterator;
+pub use crate::config::{
+    AudioCodec,
+    AudioContainer,
+    AudioFormatDescriptor,
+    SpeechConfig,
+    SpeechSynthesisOutputFormat,
+    SpeechTranslationConfig,
+};
 pub use crate::property::PropertyBag;
 pub use crate::property::PropertyId;
 use crate::speech_api::*;
 
 pub mod audio;
+pub mod cache;
+pub mod long_text;
 pub mod recognizer;
+pub mod ssml;
 pub mod synthesizer;
 mod async_handle;
+mod blocking;
 mod speech_api;
 mod property;
 mod config;
 
 const SPXHANDLE_INVALID: SPXHANDLE = 0 as SPXHANDLE;
 
+const SPXERR_CONTAINER_FORMAT_NOT_SUPPORTED: SPXHR = 0x28;
+const SPXERR_GSTREAMER_NOT_FOUND: SPXHR = 0x29;
+
+// `ffi::NulError` and `ffi::IntoStringError` are stable since 1.0,
+// clippy reports them as 1.64 because of their `alloc::ffi` re-export
+#[allow(clippy::incompatible_msrv)]
 #[derive(Fail, Debug)]
 pub enum SpxError {
     #[fail(display = "Speech API error code: {}.", _0)]
@@ -46,14 +68,34 @@ pub enum SpxError {
     FromUtf8Error(#[cause] std::string::FromUtf8Error),
     #[fail(display = "Stream is dropped.")]
     StreamDropped,
+    #[fail(display = "Result buffer returned by Speech API is malformed.")]
+    InvalidResultBuffer,
+    #[fail(display = "Failed to parse JSON.")]
+    JsonError(#[cause] serde_json::Error),
+    #[fail(display = "Unknown speech synthesis output format: {}.", _0)]
+    UnknownOutputFormat(String),
+    #[fail(display = "Invalid SSML: {}.", _0)]
+    InvalidSsml(String),
+    #[fail(display = "Speech synthesis canceled: {:?}, {:?}.", _0, _1)]
+    SynthesisCanceled(CancellationReason, CancellationErrorCode),
+    #[fail(display = "I/O error.")]
+    IoError(#[cause] std::io::Error),
+    #[fail(display = "GStreamer is required to decode compressed audio but was not found.")]
+    GstreamerNotFound,
+    #[fail(display = "Audio container format is not supported.")]
+    ContainerFormatNotSupported,
+    #[fail(display = "Invalid wave file: {}.", _0)]
+    InvalidWaveFile(String),
 }
 
+#[allow(clippy::incompatible_msrv)]
 impl From<ffi::NulError> for SpxError {
     fn from(err: ffi::NulError) -> Self {
         return SpxError::StrNulError(err);
     }
 }
 
+#[allow(clippy::incompatible_msrv)]
 impl From<ffi::IntoStringError> for SpxError {
     fn from(err: ffi::IntoStringError) -> Self {
         return SpxError::IntoStringError(err);
@@ -66,6 +108,18 @@ impl From<std::string::FromUtf8Error> for SpxError {
     }
 }
 
+impl From<serde_json::Error> for SpxError {
+    fn from(err: serde_json::Error) -> Self {
+        SpxError::JsonError(err)
+    }
+}
+
+impl From<std::io::Error> for SpxError {
+    fn from(err: std::io::Error) -> Self {
+        SpxError::IoError(err)
+    }
+}
+
 enum_from_primitive! {
 #[derive(Debug, PartialEq)]
 pub enum ResultReason {
@@ -121,6 +175,21 @@ pub enum ResultReason {
     /// Indicates the synthesized audio is now complete for this phrase.
     /// </summary>
     SynthesizingAudioCompleted = 9,
+
+    /// <summary>
+    /// Indicates the speech result contains (unverified) keyword text.
+    /// </summary>
+    RecognizingKeyword = 10,
+
+    /// <summary>
+    /// Indicates that keyword recognition completed recognizing the given keyword.
+    /// </summary>
+    RecognizedKeyword = 11,
+
+    /// <summary>
+    /// Indicates the speech synthesis is now started
+    /// </summary>
+    SynthesizingAudioStart = 12,
 }
 }
 
@@ -152,8 +221,11 @@ pub enum CancellationErrorCode
 
 #[inline(always)]
 fn convert_err(hr: usize) -> Result<(), SpxError> {
-    if hr != SPX_NOERROR as usize {
-        return Err(SpxError::ApiError(hr));
+    match hr {
+        SPXERR_CONTAINER_FORMAT_NOT_SUPPORTED => return Err(SpxError::ContainerFormatNotSupported),
+        SPXERR_GSTREAMER_NOT_FOUND => return Err(SpxError::GstreamerNotFound),
+        _ if hr != SPX_NOERROR as usize => return Err(SpxError::ApiError(hr)),
+        _ => {}
     }
     return Ok(());
 }
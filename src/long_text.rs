@@ -0,0 +1,570 @@
+//! Synthesis of texts that exceed the size limit of a single request.
+//!
+//! The input is split at sentence and paragraph boundaries, the chunks are synthesized concurrently
+//! and the audio is reassembled in the original order.
+
+use futures::prelude::*;
+use futures::stream;
+
+use crate::audio::wav;
+use crate::AudioContainer;
+use crate::SpeechConfig;
+use crate::SpeechSynthesisOutputFormat;
+use crate::SpxError;
+use crate::synthesizer::SpeechSynthesizer;
+
+const DEFAULT_MAX_CHUNK_CHARS: usize = 1000;
+// elements whose content must not be split
+const ATOMIC_ELEMENTS: [&str; 3] = ["say-as", "sub", "phoneme"];
+
+/// Splits long texts into requests of at most `max_chunk_chars` characters
+/// and runs up to `max_in_flight` of them at the same time, each on its own synthesizer.
+/// The returned futures and streams must be run on a tokio runtime.
+pub struct LongTextSynthesizer {
+    synthesizers: Vec<SpeechSynthesizer>,
+    format: SpeechSynthesisOutputFormat,
+    riff: bool,
+    max_chunk_chars: usize,
+}
+
+impl LongTextSynthesizer {
+    pub fn from_config(config: &SpeechConfig, max_in_flight: usize) -> Result<LongTextSynthesizer, SpxError> {
+        let format = config.audio_output_format()?.unwrap_or(SpeechSynthesisOutputFormat::DEFAULT);
+        let synthesizers = (0..max_in_flight.max(1))
+            .map(|_| SpeechSynthesizer::from_config(config, None))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(LongTextSynthesizer {
+            synthesizers,
+            format,
+            riff: format.descriptor().container == AudioContainer::Riff,
+            max_chunk_chars: DEFAULT_MAX_CHUNK_CHARS,
+        })
+    }
+
+    pub fn set_max_chunk_chars(&mut self, max_chunk_chars: usize) {
+        self.max_chunk_chars = max_chunk_chars.max(1);
+    }
+
+    /// Audio of the chunks in order. With a RIFF output format only the first item carries
+    /// a wave header, its sizes are set to `u32::MAX` as they are not known in advance.
+    /// A text without anything to speak results in a wave header without samples.
+    pub fn speak_text_stream(&self, text: impl AsRef<str>) -> Result<impl Stream<Item=Vec<u8>, Error=SpxError>, SpxError> {
+        let chunks = split_text(text.as_ref(), self.max_chunk_chars);
+        self.speak_chunks(chunks, false)
+    }
+
+    /// Like `speak_text_stream`, `ssml` is split into several complete SSML documents.
+    pub fn speak_ssml_stream(&self, ssml: impl AsRef<str>) -> Result<impl Stream<Item=Vec<u8>, Error=SpxError>, SpxError> {
+        let chunks = split_ssml(ssml.as_ref(), self.max_chunk_chars)?;
+        self.speak_chunks(chunks, true)
+    }
+
+    /// Resolves to the whole audio, a single valid wave file with a RIFF output format.
+    pub fn speak_text_async(&self, text: impl AsRef<str>) -> Result<impl Future<Item=Vec<u8>, Error=SpxError>, SpxError> {
+        Ok(self.speak_text_stream(text)?.concat2().map(fix_riff_sizes))
+    }
+
+    pub fn speak_ssml_async(&self, ssml: impl AsRef<str>) -> Result<impl Future<Item=Vec<u8>, Error=SpxError>, SpxError> {
+        Ok(self.speak_ssml_stream(ssml)?.concat2().map(fix_riff_sizes))
+    }
+
+    fn speak_chunks(&self, chunks: Vec<String>, ssml: bool) -> Result<impl Stream<Item=Vec<u8>, Error=SpxError>, SpxError> {
+        // handles start lazily, `buffered` keeps at most one request per synthesizer in flight
+        let handles = chunks.iter()
+            .zip(self.synthesizers.iter().cycle())
+            .map(|(chunk, synthesizer)| {
+                if ssml {
+                    synthesizer.speak_ssml_async(chunk)
+                } else {
+                    synthesizer.speak_text_async(chunk)
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let riff = self.riff;
+        let empty = if riff && handles.is_empty() {
+            Some(wav::empty_wave(&self.format.descriptor()))
+        } else {
+            None
+        };
+        let mut first = true;
+        Ok(stream::iter_ok(empty).chain(stream::iter_ok(handles)
+            .buffered(self.synthesizers.len())
+            .and_then(move |result| {
+                let audio = result.completed_audio_data()?;
+                let audio = if riff { join_riff(audio, first) } else { audio };
+                first = false;
+                Ok(audio)
+            })))
+    }
+}
+
+// RIFF
+
+/// Offset and length of the data chunk of a wave file.
+fn riff_data_range(buf: &[u8]) -> Option<(usize, usize)> {
+    if buf.len() < 12 || &buf[0..4] != b"RIFF" || &buf[8..12] != b"WAVE" {
+        return None;
+    }
+    let mut pos = 12;
+    while pos + 8 <= buf.len() {
+        let size = read_u32_le(&buf[pos + 4..pos + 8]) as usize;
+        let data_start = pos + 8;
+        if &buf[pos..pos + 4] == b"data" {
+            return Some((data_start, size.min(buf.len() - data_start)));
+        }
+        // chunks are padded to an even size
+        pos = data_start.checked_add(size)?.checked_add(size & 1)?;
+    }
+    None
+}
+
+/// Keeps the header of the first chunk with unknown sizes, strips it from the others.
+fn join_riff(mut audio: Vec<u8>, first: bool) -> Vec<u8> {
+    match riff_data_range(&audio) {
+        None => audio,
+        Some((start, len)) if first => {
+            audio.truncate(start + len);
+            write_u32_le(&mut audio[4..8], u32::MAX);
+            write_u32_le(&mut audio[start - 4..start], u32::MAX);
+            audio
+        }
+        Some((start, len)) => audio[start..start + len].to_vec(),
+    }
+}
+
+fn fix_riff_sizes(mut audio: Vec<u8>) -> Vec<u8> {
+    if let Some((start, len)) = riff_data_range(&audio) {
+        let riff_size = (audio.len() - 8).min(u32::MAX as usize) as u32;
+        write_u32_le(&mut audio[4..8], riff_size);
+        write_u32_le(&mut audio[start - 4..start], len.min(u32::MAX as usize) as u32);
+    }
+    audio
+}
+
+#[inline]
+fn read_u32_le(b: &[u8]) -> u32 {
+    u32::from(b[0]) | u32::from(b[1]) << 8 | u32::from(b[2]) << 16 | u32::from(b[3]) << 24
+}
+
+#[inline]
+fn write_u32_le(b: &mut [u8], v: u32) {
+    b[0] = v as u8;
+    b[1] = (v >> 8) as u8;
+    b[2] = (v >> 16) as u8;
+    b[3] = (v >> 24) as u8;
+}
+
+// Splitting
+
+/// Splits after sentence punctuation and at line breaks, segments longer than `max_chars`
+/// are split at whitespace, or anywhere if there is none.
+fn segments(text: &str, max_chars: usize) -> Vec<&str> {
+    let mut result = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let next = chars.peek().map(|&(_, n)| n);
+        let boundary = match c {
+            '\n' => true,
+            '.' | '!' | '?' | ';' => next.map_or(true, char::is_whitespace),
+            '。' | '！' | '？' | '；' => true,
+            _ => false,
+        };
+        if boundary {
+            // trailing whitespace stays with the sentence
+            let mut end = i + c.len_utf8();
+            while let Some(&(j, n)) = chars.peek() {
+                if !n.is_whitespace() {
+                    break;
+                }
+                end = j + n.len_utf8();
+                chars.next();
+            }
+            result.push(&text[start..end]);
+            start = end;
+        }
+    }
+    if start < text.len() {
+        result.push(&text[start..]);
+    }
+    result.into_iter().flat_map(|x| split_long(x, max_chars)).collect()
+}
+
+fn split_long(s: &str, max_chars: usize) -> Vec<&str> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    let mut start = 0;
+    while s[start..].chars().count() > max_chars {
+        let rest = &s[start..];
+        let limit = rest.char_indices().nth(max_chars).map(|(i, _)| i).unwrap_or(rest.len());
+        let end = rest[..limit].rfind(char::is_whitespace)
+            .map(|i| i + rest[i..].chars().next().map_or(1, char::len_utf8))
+            .unwrap_or(limit);
+        let end = reference_boundary(rest, end);
+        // whitespace only pieces would become chunks without anything to speak
+        match ranges.last_mut() {
+            Some(last) if s[last.0..last.1].trim().is_empty() || rest[..end].trim().is_empty() => {
+                last.1 = start + end;
+            }
+            _ => ranges.push((start, start + end)),
+        }
+        start += end;
+    }
+    if start < s.len() {
+        match ranges.last_mut() {
+            Some(last) if s[last.0..last.1].trim().is_empty() || s[start..].trim().is_empty() => last.1 = s.len(),
+            _ => ranges.push((start, s.len())),
+        }
+    }
+    ranges.into_iter().map(|(a, b)| &s[a..b]).collect()
+}
+
+/// Moves a split at `end` out of a character or entity reference like `&amp;`,
+/// to its beginning or, if the reference starts `s`, to its end.
+fn reference_boundary(s: &str, end: usize) -> usize {
+    let amp = match s[..end].rfind('&') {
+        Some(i) if !s[i..end].contains(|c: char| c == ';' || c.is_whitespace()) => i,
+        _ => return end,
+    };
+    match s[end..].find(|c: char| c == ';' || c == '&' || c.is_whitespace()) {
+        Some(i) if s[end + i..].starts_with(';') => if amp > 0 { amp } else { end + i + 1 },
+        _ => end,
+    }
+}
+
+fn split_text(text: &str, max_chars: usize) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut current = String::new();
+    let mut current_chars = 0;
+    for segment in segments(text, max_chars) {
+        let n = segment.chars().count();
+        if current_chars > 0 && current_chars + n > max_chars {
+            result.push(current.trim().to_owned());
+            current.clear();
+            current_chars = 0;
+        }
+        current.push_str(segment);
+        current_chars += n;
+    }
+    result.push(current.trim().to_owned());
+    result.retain(|x| !x.is_empty());
+    result
+}
+
+#[derive(Debug)]
+enum Token<'a> {
+    Open(&'a str, &'a str),
+    Close(&'a str, &'a str),
+    Empty(&'a str),
+    Text(&'a str),
+    // comments, processing instructions and declarations
+    Other(&'a str),
+}
+
+fn tokenize(ssml: &str) -> Result<Vec<Token<'_>>, SpxError> {
+    let mut result = Vec::new();
+    let mut rest = ssml;
+    while !rest.is_empty() {
+        if !rest.starts_with('<') {
+            let end = rest.find('<').unwrap_or(rest.len());
+            result.push(Token::Text(&rest[..end]));
+            rest = &rest[end..];
+            continue;
+        }
+        let terminator = if rest.starts_with("<!--") {
+            "-->"
+        } else if rest.starts_with("<![CDATA[") {
+            "]]>"
+        } else if rest.starts_with("<?") {
+            "?>"
+        } else {
+            ""
+        };
+        let end = if terminator.is_empty() {
+            tag_end(rest)
+        } else {
+            rest.find(terminator).map(|i| i + terminator.len())
+        }.ok_or_else(|| SpxError::InvalidSsml("unterminated tag".to_owned()))?;
+        let tag = &rest[..end];
+        rest = &rest[end..];
+        if !terminator.is_empty() || tag.starts_with("<!") {
+            result.push(Token::Other(tag));
+        } else if let Some(name) = tag.strip_prefix("</") {
+            result.push(Token::Close(tag_name(name), tag));
+        } else if tag.ends_with("/>") {
+            result.push(Token::Empty(tag));
+        } else {
+            result.push(Token::Open(tag_name(&tag[1..]), tag));
+        }
+    }
+    Ok(result)
+}
+
+/// End of a tag starting at the beginning of `s`, quoted attribute values may contain `>`.
+fn tag_end(s: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '>') => return Some(i + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn tag_name(s: &str) -> &str {
+    let end = s.find(|c: char| c.is_whitespace() || c == '/' || c == '>').unwrap_or(s.len());
+    &s[..end]
+}
+
+/// Every chunk is a complete document: elements open at a split are closed at the end
+/// of the chunk and opened again at the beginning of the next one.
+fn split_ssml(ssml: &str, max_chars: usize) -> Result<Vec<String>, SpxError> {
+    let tokens = tokenize(ssml)?;
+    let root = tokens.iter()
+        .position(|x| match x {
+            Token::Open(name, _) => *name == "speak",
+            _ => false,
+        })
+        .ok_or_else(|| SpxError::InvalidSsml("missing speak element".to_owned()))?;
+    let prefix: String = tokens[..=root].iter().map(|x| x.as_str()).collect();
+
+    let mut chunks = Vec::new();
+    let mut chunk = SsmlChunk::default();
+    let mut stack: Vec<(&str, &str)> = Vec::new();
+    let mut closed = false;
+    for token in tokens[root + 1..].iter() {
+        match token {
+            Token::Open(name, raw) => {
+                stack.push((name, raw));
+                chunk.text.push_str(raw);
+                chunk.has_content |= *name == "audio";
+            }
+            Token::Close(name, raw) => {
+                match stack.pop() {
+                    Some((open, _)) if open == *name => chunk.push(raw, &stack),
+                    None if *name == "speak" => {
+                        closed = true;
+                        break;
+                    }
+                    _ => return Err(SpxError::InvalidSsml(format!("unexpected closing tag {}", name))),
+                }
+            }
+            Token::Empty(raw) => {
+                chunk.push(raw, &stack);
+                chunk.has_content = true;
+            }
+            Token::Other(raw) => {
+                chunk.push(raw, &stack);
+                chunk.has_content |= raw.starts_with("<![CDATA[");
+            }
+            Token::Text(text) => {
+                let atomic = stack.iter().any(|(name, _)| ATOMIC_ELEMENTS.contains(name));
+                let parts = if atomic { vec![*text] } else { segments(text, max_chars) };
+                for part in parts {
+                    let n = part.chars().count();
+                    if chunk.chars > 0 && chunk.chars + n > max_chars {
+                        chunks.extend(chunk.finish(&prefix, &stack));
+                        chunk = SsmlChunk::reopen(&stack);
+                    }
+                    chunk.push(part, &stack);
+                    chunk.chars += n;
+                    chunk.has_content |= !part.trim().is_empty();
+                }
+            }
+        }
+    }
+    if !closed || !stack.is_empty() {
+        return Err(SpxError::InvalidSsml("unbalanced elements".to_owned()));
+    }
+    chunks.extend(chunk.finish(&prefix, &stack));
+    Ok(chunks)
+}
+
+#[derive(Default)]
+struct SsmlChunk {
+    text: String,
+    chars: usize,
+    has_content: bool,
+    // end of the last content and the depth at that point,
+    // elements opened after it are moved to the next chunk
+    mark: (usize, usize),
+}
+
+impl SsmlChunk {
+    fn reopen(stack: &[(&str, &str)]) -> SsmlChunk {
+        let mut result = SsmlChunk::default();
+        for (_, open) in stack.iter() {
+            result.text.push_str(open);
+        }
+        result.mark = (result.text.len(), stack.len());
+        result
+    }
+
+    fn push(&mut self, s: &str, stack: &[(&str, &str)]) {
+        self.text.push_str(s);
+        self.mark = (self.text.len(), stack.len());
+    }
+
+    fn finish(mut self, prefix: &str, stack: &[(&str, &str)]) -> Option<String> {
+        if !self.has_content {
+            return None;
+        }
+        let (len, depth) = self.mark;
+        self.text.truncate(len);
+        let mut result = prefix.to_owned();
+        result.push_str(&self.text);
+        for (name, _) in stack[..depth].iter().rev() {
+            result.push_str("</");
+            result.push_str(name);
+            result.push('>');
+        }
+        result.push_str("</speak>");
+        Some(result)
+    }
+}
+
+impl<'a> Token<'a> {
+    fn as_str(&self) -> &'a str {
+        match self {
+            Token::Open(_, raw) | Token::Close(_, raw) => raw,
+            Token::Empty(raw) | Token::Text(raw) | Token::Other(raw) => raw,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPEAK: &str = r#"<speak version="1.0" xml:lang="en-US">"#;
+
+    fn doc(body: &str) -> String {
+        format!("{}{}</speak>", SPEAK, body)
+    }
+
+    fn wave(data: &[u8], trailer: &[u8]) -> Vec<u8> {
+        let mut result = b"RIFF\0\0\0\0WAVEfmt ".to_vec();
+        result.extend_from_slice(&[16, 0, 0, 0]);
+        result.extend_from_slice(&[0u8; 16]);
+        result.extend_from_slice(b"data");
+        result.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        result.extend_from_slice(data);
+        result.extend_from_slice(trailer);
+        result
+    }
+
+    #[test]
+    fn splits_text_at_sentences() {
+        assert_eq!(split_text("One. Two! Three? Four", 10), vec!["One. Two!", "Three?", "Four"]);
+        assert_eq!(split_text("a.b c. d", 100), vec!["a.b c. d"]);
+        assert_eq!(split_text("Line one\nLine two", 10), vec!["Line one", "Line two"]);
+        assert_eq!(split_text("  \n ", 10), Vec::<String>::new());
+    }
+
+    #[test]
+    fn splits_long_sentences_at_whitespace() {
+        assert_eq!(split_text("aaa bbb ccc ddd", 8), vec!["aaa bbb", "ccc ddd"]);
+        assert_eq!(split_text("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+        assert_eq!(split_text("äöüß äöüß", 5), vec!["äöüß", "äöüß"]);
+    }
+
+    #[test]
+    fn does_not_emit_whitespace_only_pieces() {
+        for piece in split_long("abc          def", 3) {
+            assert!(!piece.trim().is_empty(), "{:?}", piece);
+        }
+        assert_eq!(split_long("abc          def", 3).concat(), "abc          def");
+        assert_eq!(split_long("      abc", 3), vec!["      abc"]);
+    }
+
+    #[test]
+    fn does_not_split_references() {
+        assert_eq!(split_long("a&amp;b", 3), vec!["a", "&amp;", "b"]);
+        assert_eq!(split_long("abc&#169;def", 5), vec!["abc", "&#169;", "def"]);
+        assert_eq!(split_long("AT&T and", 3), vec!["AT&", "T ", "and"]);
+        let ssml = doc("Tom&amp;Jerry&apos;s");
+        for chunk in split_ssml(&ssml, 5).unwrap() {
+            let text = &chunk[SPEAK.len()..chunk.len() - "</speak>".len()];
+            assert!(["Tom", "&amp;", "Jerry", "&apos;", "s"].contains(&text), "{}", chunk);
+        }
+    }
+
+    #[test]
+    fn splits_ssml_into_complete_documents() {
+        let ssml = doc(r#"<voice name="a"><prosody rate="+10%">One. Two.</prosody></voice>"#);
+        assert_eq!(split_ssml(&ssml, 5).unwrap(), vec![
+            doc(r#"<voice name="a"><prosody rate="+10%">One. </prosody></voice>"#),
+            doc(r#"<voice name="a"><prosody rate="+10%">Two.</prosody></voice>"#),
+        ]);
+    }
+
+    #[test]
+    fn moves_elements_opened_at_a_split_to_the_next_chunk() {
+        let ssml = doc("<voice name=\"a\">One. <emphasis>Two.</emphasis></voice>");
+        assert_eq!(split_ssml(&ssml, 5).unwrap(), vec![
+            doc("<voice name=\"a\">One. </voice>"),
+            doc("<voice name=\"a\"><emphasis>Two.</emphasis></voice>"),
+        ]);
+    }
+
+    #[test]
+    fn keeps_atomic_elements_together() {
+        let ssml = doc(r#"Call <say-as interpret-as="telephone">555 1234 5678</say-as> now."#);
+        let chunks = split_ssml(&ssml, 8).unwrap();
+        assert_eq!(chunks, vec![
+            doc("Call "),
+            doc(r#"<say-as interpret-as="telephone">555 1234 5678</say-as>"#),
+            doc(" now."),
+        ]);
+    }
+
+    #[test]
+    fn keeps_comments_and_empty_elements() {
+        let ssml = doc(r#"One.<!-- c --><break time="1s"/> Two."#);
+        assert_eq!(split_ssml(&ssml, 100).unwrap(), vec![ssml.clone()]);
+        assert_eq!(split_ssml(&ssml, 4).unwrap(), vec![
+            doc(r#"One.<!-- c --><break time="1s"/>"#),
+            doc(" Two."),
+        ]);
+    }
+
+    #[test]
+    fn rejects_malformed_ssml() {
+        for ssml in &["no root", "<speak><p>x</speak>", "<speak>x</p></speak>", "<speak>x", "<speak><p"] {
+            match split_ssml(ssml, 10) {
+                Err(SpxError::InvalidSsml(_)) => {}
+                x => panic!("{}: expected InvalidSsml, got {:?}", ssml, x),
+            }
+        }
+    }
+
+    #[test]
+    fn empty_wave_has_empty_data_chunk() {
+        let empty = wav::empty_wave(&SpeechSynthesisOutputFormat::DEFAULT.descriptor());
+        assert_eq!(riff_data_range(&empty), Some((44, 0)));
+        assert_eq!(fix_riff_sizes(empty.clone()), empty);
+    }
+
+    #[test]
+    fn joins_wave_chunks() {
+        let first = join_riff(wave(&[1, 2, 3, 4], b"LIST\x02\0\0\0ab"), true);
+        let second = join_riff(wave(&[5, 6], &[]), false);
+        assert_eq!(first, {
+            let mut x = wave(&[1, 2, 3, 4], &[]);
+            x[4..8].copy_from_slice(&[0xff; 4]);
+            x[40..44].copy_from_slice(&[0xff; 4]);
+            x
+        });
+        assert_eq!(second, vec![5, 6]);
+        assert_eq!(join_riff(vec![9, 9], false), vec![9, 9]);
+
+        let mut joined = first;
+        joined.extend(second);
+        let mut expected = wave(&[1, 2, 3, 4, 5, 6], &[]);
+        let riff_size = (expected.len() - 8) as u32;
+        expected[4..8].copy_from_slice(&riff_size.to_le_bytes());
+        assert_eq!(fix_riff_sizes(joined), expected);
+    }
+}
@@ -0,0 +1,119 @@
+use std::ffi::c_void;
+use std::ptr;
+
+use futures::sync::mpsc::{channel, Receiver, Sender};
+
+use crate::convert_err;
+use crate::recognizer::DEFAULT_CH_BUFF_SIZE;
+use crate::recognizer::events::ConnectionEvent;
+use crate::recognizer::events::EventFactory;
+use crate::SmartHandle;
+use crate::speech_api::*;
+use crate::SpxError;
+use crate::SPXHANDLE_INVALID;
+
+type ConnectionSetCallbackFn = unsafe extern "C" fn(SPXCONNECTIONHANDLE, CONNECTION_CALLBACK_FUNC, *mut c_void) -> SPXHR;
+
+/// Service connection of a recognizer, can be used to connect in advance and to observe connection state.
+pub struct Connection {
+    handle: SmartHandle<SPXCONNECTIONHANDLE>,
+    connected_sender: Option<Box<Sender<ConnectionEvent>>>,
+    disconnected_sender: Option<Box<Sender<ConnectionEvent>>>,
+}
+
+impl Connection {
+    pub(crate)
+    fn from_recognizer_handle(hreco: SPXRECOHANDLE) -> Result<Connection, SpxError> {
+        let mut handle = SPXHANDLE_INVALID;
+        unsafe {
+            convert_err(connection_from_recognizer(hreco, &mut handle))?;
+        }
+        Ok(Connection {
+            handle: SmartHandle::create("Connection", handle, connection_handle_release),
+            connected_sender: None,
+            disconnected_sender: None,
+        })
+    }
+
+    #[inline]
+    pub fn get_handle(&self) -> SPXCONNECTIONHANDLE {
+        self.handle.get()
+    }
+
+    /// Connects to the service, `for_continuous_recognition` should match the recognition mode started later.
+    pub fn open(&mut self, for_continuous_recognition: bool) -> Result<(), SpxError> {
+        unsafe {
+            convert_err(connection_open(self.get_handle(), for_continuous_recognition))
+        }
+    }
+
+    pub fn close(&mut self) -> Result<(), SpxError> {
+        unsafe {
+            convert_err(connection_close(self.get_handle()))
+        }
+    }
+
+    pub fn connect_connected(&mut self, buff_size: Option<usize>) -> Result<Receiver<ConnectionEvent>, SpxError> {
+        let (s, r) = channel(buff_size.unwrap_or(DEFAULT_CH_BUFF_SIZE));
+        let sender = Some(Box::new(s));
+        self.set_callback(&sender, connection_connected_set_callback)?;
+        self.connected_sender = sender;
+        Ok(r)
+    }
+
+    pub fn connect_disconnected(&mut self, buff_size: Option<usize>) -> Result<Receiver<ConnectionEvent>, SpxError> {
+        let (s, r) = channel(buff_size.unwrap_or(DEFAULT_CH_BUFF_SIZE));
+        let sender = Some(Box::new(s));
+        self.set_callback(&sender, connection_disconnected_set_callback)?;
+        self.disconnected_sender = sender;
+        Ok(r)
+    }
+
+    fn set_callback(&self,
+                    sender: &Option<Box<Sender<ConnectionEvent>>>,
+                    f: ConnectionSetCallbackFn) -> Result<(), SpxError> {
+        if let Some(s) = sender {
+            let s = s.as_ref();
+            unsafe {
+                convert_err(f(self.get_handle(), Some(Self::cb_send), s as *const _ as *mut c_void))?;
+            }
+        } else {
+            unsafe {
+                convert_err(f(self.get_handle(), None, ptr::null_mut()))?;
+            }
+        }
+        Ok(())
+    }
+
+    unsafe extern "C" fn cb_send(h_evt: SPXEVENTHANDLE, p_sender: *mut c_void) {
+        let sender = &mut *(p_sender as *mut Sender<ConnectionEvent>);
+        let event = match ConnectionEvent::create(h_evt) {
+            Ok(x) => x,
+            Err(e) => {
+                error!("can not create event, err: {}", e);
+                return;
+            }
+        };
+        match sender.try_send(event) {
+            Ok(()) => {}
+            Err(e) => {
+                error!("can not publish event, err: {}", e);
+            }
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        if self.connected_sender.is_some() {
+            if let Err(e) = self.set_callback(&None, connection_connected_set_callback) {
+                error!("can not disconnect connected callback, err: {}", e);
+            }
+        }
+        if self.disconnected_sender.is_some() {
+            if let Err(e) = self.set_callback(&None, connection_disconnected_set_callback) {
+                error!("can not disconnect disconnected callback, err: {}", e);
+            }
+        }
+    }
+}
@@ -7,6 +7,7 @@ use num::FromPrimitive;
 use crate::CancellationErrorCode;
 use crate::CancellationReason;
 use crate::FromHandle;
+use crate::recognizer::shared_result_handle;
 use crate::SmartHandle;
 use crate::speech_api::*;
 use crate::SpxError;
@@ -72,6 +73,29 @@ impl SessionEvent {
     }
 }
 
+// ConnectionEvent
+
+pub struct ConnectionEvent {
+    base: SessionEvent,
+}
+
+impl Deref for ConnectionEvent {
+    type Target = SessionEvent;
+
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl EventFactory for ConnectionEvent {
+    #[inline]
+    fn create(handle: SPXEVENTHANDLE) -> Result<ConnectionEvent, SpxError> {
+        Ok(ConnectionEvent {
+            base: SessionEvent::create(handle)?,
+        })
+    }
+}
+
 // RecognitionEvent
 
 pub struct RecognitionEvent {
@@ -132,7 +156,7 @@ impl BaseRecognitionResultEvent {
     #[inline(always)]
     fn get_result_handle(event_handle: SPXEVENTHANDLE) -> Result<Arc<SmartHandle<SPXRESULTHANDLE>>, SpxError> {
         let handle = crate::spx_populate(event_handle, recognizer_recognition_event_get_result)?;
-        Ok(Arc::new(SmartHandle::create("RecognitionResult", handle, recognizer_result_handle_release)))
+        Ok(shared_result_handle("RecognitionResult", handle))
     }
 }
 
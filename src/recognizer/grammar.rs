@@ -0,0 +1,70 @@
+use std::ffi::CString;
+
+use crate::convert_err;
+use crate::SmartHandle;
+use crate::speech_api::*;
+use crate::SpxError;
+use crate::SPXHANDLE_INVALID;
+
+/// Phrases that should be favored by the recognizer, e.g. names or domain specific terms.
+#[derive(Debug)]
+pub struct PhraseListGrammar {
+    handle: SmartHandle<SPXGRAMMARHANDLE>,
+}
+
+impl PhraseListGrammar {
+    pub(crate)
+    fn from_recognizer_handle(hreco: SPXRECOHANDLE) -> Result<PhraseListGrammar, SpxError> {
+        let c_name = CString::new("")?;
+        let mut handle = SPXHANDLE_INVALID;
+        unsafe {
+            convert_err(phrase_list_grammar_from_recognizer_by_name(&mut handle, hreco, c_name.as_ptr()))?;
+        }
+        Ok(PhraseListGrammar {
+            handle: SmartHandle::create("PhraseListGrammar", handle, grammar_handle_release),
+        })
+    }
+
+    #[inline]
+    pub fn get_handle(&self) -> SPXGRAMMARHANDLE {
+        self.handle.get()
+    }
+
+    pub fn add_phrase(&mut self, text: impl AsRef<str>) -> Result<(), SpxError> {
+        let phrase = GrammarPhrase::from_text(text)?;
+        unsafe {
+            convert_err(phrase_list_grammar_add_phrase(self.get_handle(), phrase.handle.get()))
+        }
+    }
+
+    pub fn add_phrases<I, S>(&mut self, phrases: I) -> Result<(), SpxError>
+        where I: IntoIterator<Item=S>, S: AsRef<str> {
+        for phrase in phrases {
+            self.add_phrase(phrase)?;
+        }
+        Ok(())
+    }
+
+    pub fn clear(&mut self) -> Result<(), SpxError> {
+        unsafe {
+            convert_err(phrase_list_grammar_clear(self.get_handle()))
+        }
+    }
+}
+
+struct GrammarPhrase {
+    handle: SmartHandle<SPXPHRASEHANDLE>,
+}
+
+impl GrammarPhrase {
+    fn from_text(text: impl AsRef<str>) -> Result<GrammarPhrase, SpxError> {
+        let c_text = CString::new(text.as_ref())?;
+        let mut handle = SPXHANDLE_INVALID;
+        unsafe {
+            convert_err(grammar_phrase_create_from_text(&mut handle, c_text.as_ptr()))?;
+        }
+        Ok(GrammarPhrase {
+            handle: SmartHandle::create("GrammarPhrase", handle, grammar_phrase_handle_release),
+        })
+    }
+}
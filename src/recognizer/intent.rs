@@ -0,0 +1,236 @@
+use std::borrow::Borrow;
+use std::ffi::CString;
+use std::ops::Deref;
+use std::ops::DerefMut;
+use std::sync::Arc;
+
+use crate::audio::AudioConfig;
+use crate::BlockingRecognizer;
+use crate::convert_err;
+use crate::FromHandle;
+use crate::recognizer::AbstractAsyncRecognizer;
+use crate::recognizer::AsyncRecognizer;
+use crate::recognizer::events::RecognitionCanceledEvent;
+use crate::recognizer::events::RecognitionResultEvent;
+use crate::recognizer::LanguageUnderstandingModel;
+use crate::recognizer::LanguageUnderstandingResult;
+use crate::recognizer::RecognitionResult;
+use crate::recognizer::Recognizer;
+use crate::recognizer::shared_result_handle;
+use crate::PropertyBag;
+use crate::PropertyId;
+use crate::ResultHandleSupport;
+use crate::SmartHandle;
+use crate::speech_api::*;
+use crate::SpeechConfig;
+use crate::SpxError;
+use crate::SPXHANDLE_INVALID;
+
+const MAX_INTENT_ID_CHAR_COUNT: usize = 1024;
+
+type R = IntentRecognitionResult;
+type E = RecognitionResultEvent<R>;
+type C = RecognitionCanceledEvent;
+
+pub struct IntentRecognizer<CFG> {
+    base: AbstractAsyncRecognizer<E, C>,
+    #[allow(unused)]
+    config: CFG,
+    #[allow(unused)]
+    audio: Option<AudioConfig>,
+}
+
+impl<CFG> IntentRecognizer<CFG>
+    where CFG: Borrow<SpeechConfig> {
+    pub fn from_config(config: CFG, audio: Option<AudioConfig>) -> Result<IntentRecognizer<CFG>, SpxError> {
+        let mut handle = SPXHANDLE_INVALID;
+        unsafe {
+            convert_err(
+                recognizer_create_intent_recognizer_from_config(
+                    &mut handle,
+                    config.borrow().get_handle(),
+                    audio.as_ref().map(|c| c.get_handle()).unwrap_or(0 as SPXAUDIOCONFIGHANDLE),
+                )
+            )?;
+        }
+        Ok(IntentRecognizer {
+            base: AbstractAsyncRecognizer::create(handle)?,
+            config,
+            audio,
+        })
+    }
+}
+
+impl<CFG> IntentRecognizer<CFG> {
+    /// Adds a phrase that should be recognized as an intent, the phrase itself is used as the intent id.
+    pub fn add_intent(&mut self, phrase: impl AsRef<str>) -> Result<(), SpxError> {
+        let trigger = IntentTrigger::from_phrase(phrase.as_ref())?;
+        self.add_intent_trigger(&trigger, phrase)
+    }
+
+    /// Adds a phrase that should be recognized as the intent `intent_id`.
+    pub fn add_intent_with_id(&mut self, phrase: impl AsRef<str>, intent_id: impl AsRef<str>) -> Result<(), SpxError> {
+        let trigger = IntentTrigger::from_phrase(phrase)?;
+        self.add_intent_trigger(&trigger, intent_id)
+    }
+
+    /// Adds the intent `intent_name` of a LUIS model, the intent name is used as the intent id.
+    pub fn add_model_intent(&mut self, model: &LanguageUnderstandingModel, intent_name: impl AsRef<str>) -> Result<(), SpxError> {
+        let trigger = IntentTrigger::from_model(model, Some(intent_name.as_ref()))?;
+        self.add_intent_trigger(&trigger, intent_name)
+    }
+
+    /// Adds all intents of a LUIS model, results carry the LUIS intent name as intent id.
+    pub fn add_all_intents(&mut self, model: &LanguageUnderstandingModel) -> Result<(), SpxError> {
+        let trigger = IntentTrigger::from_model(model, None)?;
+        self.add_intent_trigger(&trigger, "")
+    }
+
+    pub fn add_intent_trigger(&mut self, trigger: &IntentTrigger, intent_id: impl AsRef<str>) -> Result<(), SpxError> {
+        let c_intent_id = CString::new(intent_id.as_ref())?;
+        unsafe {
+            convert_err(intent_recognizer_add_intent(
+                self.get_handle(),
+                c_intent_id.as_ptr(),
+                trigger.get_handle(),
+            ))
+        }
+    }
+}
+
+impl<CFG> Deref for IntentRecognizer<CFG> {
+    type Target = dyn AsyncRecognizer<R, E, C, Target=dyn Recognizer>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl<CFG> DerefMut for IntentRecognizer<CFG> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.base
+    }
+}
+
+impl<CFG> BlockingRecognizer<R> for IntentRecognizer<CFG> {
+    fn recognize_once_blocking(&mut self) -> Result<R, SpxError> {
+        self.base.recognize_once_blocking()
+    }
+
+    fn start_continuous_recognition_blocking(&mut self) -> Result<(), SpxError> {
+        self.base.start_continuous_recognition_blocking()
+    }
+
+    fn stop_continuous_recognition_blocking(&mut self) -> Result<(), SpxError> {
+        self.base.stop_continuous_recognition_blocking()
+    }
+}
+
+// IntentTrigger
+
+#[derive(Debug)]
+pub struct IntentTrigger {
+    handle: SmartHandle<SPXTRIGGERHANDLE>,
+}
+
+impl IntentTrigger {
+    pub fn from_phrase(phrase: impl AsRef<str>) -> Result<IntentTrigger, SpxError> {
+        let c_phrase = CString::new(phrase.as_ref())?;
+        let mut handle = SPXHANDLE_INVALID;
+        unsafe {
+            convert_err(intent_trigger_create_from_phrase(&mut handle, c_phrase.as_ptr()))?;
+        }
+        Ok(IntentTrigger::create(handle))
+    }
+
+    /// Triggers on `intent_name` of the model, or on all of its intents if `intent_name` is `None`.
+    pub fn from_model(model: &LanguageUnderstandingModel, intent_name: Option<&str>) -> Result<IntentTrigger, SpxError> {
+        let c_intent_name = intent_name.map(CString::new).transpose()?;
+        let mut handle = SPXHANDLE_INVALID;
+        unsafe {
+            convert_err(intent_trigger_create_from_language_understanding_model(
+                &mut handle,
+                model.get_handle(),
+                c_intent_name.as_ref().map(|x| x.as_ptr()).unwrap_or(std::ptr::null()),
+            ))?;
+        }
+        Ok(IntentTrigger::create(handle))
+    }
+
+    #[inline(always)]
+    fn create(handle: SPXTRIGGERHANDLE) -> IntentTrigger {
+        IntentTrigger {
+            handle: SmartHandle::create("IntentTrigger", handle, intent_trigger_handle_release),
+        }
+    }
+
+    #[inline]
+    pub fn get_handle(&self) -> SPXTRIGGERHANDLE {
+        self.handle.get()
+    }
+}
+
+// IntentRecognitionResult
+
+pub struct IntentRecognitionResult {
+    base: RecognitionResult,
+}
+
+impl IntentRecognitionResult {
+    fn create(handle: Arc<SmartHandle<SPXRESULTHANDLE>>) -> Result<IntentRecognitionResult, SpxError> {
+        Ok(IntentRecognitionResult {
+            base: RecognitionResult::create(handle)?,
+        })
+    }
+
+    /// Id of the matched intent, empty if no intent has been matched.
+    pub fn intent_id(&self) -> Result<String, SpxError> {
+        crate::spx_populate_string(self.get_handle(), MAX_INTENT_ID_CHAR_COUNT, intent_result_get_intent_id)
+    }
+
+    /// Raw LUIS response, `None` if the intent was not matched by a language understanding model.
+    pub fn language_understanding_json(&self) -> Result<Option<String>, SpxError> {
+        let props = PropertyBag::create(self.get_handle(), result_get_property_bag)?;
+        Ok(props.get(PropertyId::LanguageUnderstandingServiceResponseJsonResult)?.filter(|x| !x.is_empty()))
+    }
+
+    pub fn language_understanding_result(&self) -> Result<Option<LanguageUnderstandingResult>, SpxError> {
+        self.language_understanding_json()?
+            .map(LanguageUnderstandingResult::from_json)
+            .transpose()
+    }
+}
+
+impl Deref for IntentRecognitionResult {
+    type Target = RecognitionResult;
+
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl FromHandle<Arc<SmartHandle<SPXRESULTHANDLE>>, SpxError> for IntentRecognitionResult {
+    fn from_handle(handle: Arc<SmartHandle<SPXRESULTHANDLE>>) -> Result<IntentRecognitionResult, SpxError> {
+        IntentRecognitionResult::create(handle)
+    }
+}
+
+impl FromHandle<SPXRESULTHANDLE, SpxError> for IntentRecognitionResult {
+    fn from_handle(handle: SPXRESULTHANDLE) -> Result<IntentRecognitionResult, SpxError> {
+        IntentRecognitionResult::create(shared_result_handle("IntentRecognitionResult", handle))
+    }
+}
+
+impl ResultHandleSupport for IntentRecognitionResult {
+    fn async_wait_fn() -> unsafe extern "C" fn(SPXASYNCHANDLE, u32, *mut SPXRESULTHANDLE) -> SPXHR {
+        recognizer_recognize_once_async_wait_for
+    }
+
+    fn release_fn() -> unsafe extern "C" fn(SPXRESULTHANDLE) -> SPXHR {
+        recognizer_result_handle_release
+    }
+}
+
+unsafe impl Sync for IntentRecognitionResult {}
+
+unsafe impl Send for IntentRecognitionResult {}
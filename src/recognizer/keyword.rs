@@ -0,0 +1,42 @@
+use std::ffi::CString;
+use std::sync::Arc;
+
+use crate::convert_err;
+use crate::SmartHandle;
+use crate::speech_api::*;
+use crate::SpxError;
+use crate::SPXHANDLE_INVALID;
+
+#[derive(Debug, Clone)]
+pub struct KeywordRecognitionModel {
+    handle: Arc<KeywordModelHandle>,
+}
+
+#[derive(Debug)]
+struct KeywordModelHandle(SmartHandle<SPXKEYWORDHANDLE>);
+
+// the model is not modified after it has been loaded from file,
+// recognizers only read it when keyword recognition starts
+unsafe impl Sync for KeywordModelHandle {}
+
+impl KeywordRecognitionModel {
+    pub fn from_file(file_name: impl AsRef<str>) -> Result<KeywordRecognitionModel, SpxError> {
+        let c_file_name = CString::new(file_name.as_ref())?;
+        let mut handle = SPXHANDLE_INVALID;
+        unsafe {
+            convert_err(keyword_recognition_model_create_from_file(c_file_name.as_ptr(), &mut handle))?;
+        }
+        Ok(KeywordRecognitionModel {
+            handle: Arc::new(KeywordModelHandle(SmartHandle::create(
+                "KeywordRecognitionModel",
+                handle,
+                keyword_recognition_model_handle_release,
+            ))),
+        })
+    }
+
+    #[inline]
+    pub fn get_handle(&self) -> SPXKEYWORDHANDLE {
+        self.handle.0.get()
+    }
+}
@@ -0,0 +1,375 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::iter;
+
+use crate::convert_err;
+use crate::SmartHandle;
+use crate::speech_api::*;
+use crate::SpxError;
+use crate::SPXHANDLE_INVALID;
+
+// LanguageUnderstandingModel
+
+#[derive(Debug)]
+pub struct LanguageUnderstandingModel {
+    handle: SmartHandle<SPXLUMODELHANDLE>,
+}
+
+impl LanguageUnderstandingModel {
+    /// Creates a model from the endpoint url of a published LUIS app.
+    pub fn from_uri(uri: impl AsRef<str>) -> Result<LanguageUnderstandingModel, SpxError> {
+        let c_uri = CString::new(uri.as_ref())?;
+        Self::create(|handle| {
+            unsafe {
+                convert_err(language_understanding_model_create_from_uri(handle, c_uri.as_ptr()))
+            }
+        })
+    }
+
+    pub fn from_app_id(app_id: impl AsRef<str>) -> Result<LanguageUnderstandingModel, SpxError> {
+        let c_app_id = CString::new(app_id.as_ref())?;
+        Self::create(|handle| {
+            unsafe {
+                convert_err(language_understanding_model_create_from_app_id(handle, c_app_id.as_ptr()))
+            }
+        })
+    }
+
+    pub fn from_subscription<S1, S2, S3>(subscription: S1, app_id: S2, region: S3) -> Result<LanguageUnderstandingModel, SpxError>
+        where S1: AsRef<str>, S2: AsRef<str>, S3: AsRef<str> {
+        let c_sub = CString::new(subscription.as_ref())?;
+        let c_app_id = CString::new(app_id.as_ref())?;
+        let c_region = CString::new(region.as_ref())?;
+        Self::create(|handle| {
+            unsafe {
+                convert_err(language_understanding_model_create_from_subscription(
+                    handle,
+                    c_sub.as_ptr(),
+                    c_app_id.as_ptr(),
+                    c_region.as_ptr(),
+                ))
+            }
+        })
+    }
+
+    #[inline(always)]
+    fn create(f: impl FnOnce(&mut SPXLUMODELHANDLE) -> Result<(), SpxError>) -> Result<LanguageUnderstandingModel, SpxError> {
+        let mut handle = SPXHANDLE_INVALID;
+        f(&mut handle)?;
+        Ok(LanguageUnderstandingModel {
+            handle: SmartHandle::create("LanguageUnderstandingModel", handle, language_understanding_model__handle_release),
+        })
+    }
+
+    #[inline]
+    pub fn get_handle(&self) -> SPXLUMODELHANDLE {
+        self.handle.get()
+    }
+}
+
+// LanguageUnderstandingResult
+
+/// Typed form of `PropertyId::LanguageUnderstandingServiceResponseJsonResult`,
+/// responses of the v3 prediction API are converted to the v2 layout.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LanguageUnderstandingResult {
+    #[serde(default)]
+    pub query: String,
+    #[serde(default)]
+    pub top_scoring_intent: Option<ScoredIntent>,
+    #[serde(default)]
+    pub intents: Vec<ScoredIntent>,
+    #[serde(default)]
+    pub entities: Vec<Entity>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ScoredIntent {
+    pub intent: String,
+    #[serde(default)]
+    pub score: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Entity {
+    pub entity: String,
+    #[serde(rename = "type")]
+    pub entity_type: String,
+    /// Index of the first character of the entity in the query, in UTF-16 code units.
+    pub start_index: usize,
+    /// Index of the last character (inclusive) of the entity in the query, in UTF-16 code units.
+    pub end_index: usize,
+    #[serde(default)]
+    pub score: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct V3Response {
+    query: String,
+    prediction: V3Prediction,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct V3Prediction {
+    #[serde(default)]
+    top_intent: Option<String>,
+    #[serde(default)]
+    intents: HashMap<String, V3Intent>,
+    #[serde(default)]
+    entities: V3Entities,
+}
+
+#[derive(Deserialize)]
+struct V3Intent {
+    #[serde(default)]
+    score: Option<f64>,
+}
+
+/// Only the `$instance` metadata, it holds the position of every entity.
+#[derive(Default, Deserialize)]
+struct V3Entities {
+    #[serde(rename = "$instance", default)]
+    instances: HashMap<String, Vec<V3EntityInstance>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct V3EntityInstance {
+    #[serde(rename = "type")]
+    entity_type: String,
+    text: String,
+    start_index: usize,
+    length: usize,
+    #[serde(default)]
+    score: Option<f64>,
+}
+
+impl From<V3Response> for LanguageUnderstandingResult {
+    fn from(response: V3Response) -> LanguageUnderstandingResult {
+        let V3Prediction { top_intent, intents, entities } = response.prediction;
+        let mut intents: Vec<_> = intents.into_iter()
+            .map(|(intent, x)| ScoredIntent { intent, score: x.score })
+            .collect();
+        // highest score first, like in v2 responses
+        intents.sort_by(|a, b| {
+            b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal).then_with(|| a.intent.cmp(&b.intent))
+        });
+        let top_scoring_intent = top_intent.map(|name| {
+            let score = intents.iter().find(|x| x.intent == name).and_then(|x| x.score);
+            ScoredIntent { intent: name, score }
+        });
+        let mut entities: Vec<_> = entities.instances.into_iter()
+            .flat_map(|(_, x)| x)
+            .filter(|x| x.length > 0)
+            .map(|x| Entity {
+                entity: x.text,
+                entity_type: x.entity_type,
+                start_index: x.start_index,
+                end_index: x.start_index + x.length - 1,
+                score: x.score,
+            })
+            .collect();
+        entities.sort_by_key(|x| (x.start_index, x.end_index));
+        LanguageUnderstandingResult {
+            query: response.query,
+            top_scoring_intent,
+            intents,
+            entities,
+        }
+    }
+}
+
+impl LanguageUnderstandingResult {
+    /// Accepts responses of the v2 and the v3 prediction API.
+    pub fn from_json(json: impl AsRef<str>) -> Result<LanguageUnderstandingResult, SpxError> {
+        let value: serde_json::Value = serde_json::from_str(json.as_ref())?;
+        if value.get("prediction").is_some() {
+            let response: V3Response = serde_json::from_value(value)?;
+            Ok(response.into())
+        } else {
+            Ok(serde_json::from_value(value)?)
+        }
+    }
+
+    /// The top scoring intent, falls back to the highest scored entry of `intents`.
+    pub fn top_intent(&self) -> Option<&ScoredIntent> {
+        self.top_scoring_intent.as_ref().or_else(|| {
+            self.intents.iter().fold(None, |top: Option<&ScoredIntent>, x| match top {
+                Some(t) if t.score.unwrap_or(0.0) >= x.score.unwrap_or(0.0) => Some(t),
+                _ => Some(x),
+            })
+        })
+    }
+}
+
+impl Entity {
+    /// The part of `query` covered by this entity, `None` if the indices are out of range
+    /// or do not fall on character boundaries.
+    pub fn text<'a>(&self, query: &'a str) -> Option<&'a str> {
+        let end_index = self.end_index.checked_add(1)?;
+        if end_index <= self.start_index {
+            return None;
+        }
+        let mut start = None;
+        let mut units = 0;
+        // the service counts UTF-16 code units, characters outside of the BMP count twice
+        for (i, c) in query.char_indices().chain(iter::once((query.len(), '\0'))) {
+            if units == self.start_index {
+                start = Some(i);
+            }
+            if units == end_index {
+                return Some(&query[start?..i]);
+            }
+            if units > end_index {
+                return None;
+            }
+            units += c.len_utf16();
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const V2_RESPONSE: &str = r#"{
+        "query": "book a flight to Cairo",
+        "topScoringIntent": { "intent": "BookFlight", "score": 0.9887482 },
+        "intents": [
+            { "intent": "BookFlight", "score": 0.9887482 },
+            { "intent": "None", "score": 0.04272597 },
+            { "intent": "LocationFinder", "score": 0.0125702191 }
+        ],
+        "entities": [
+            { "entity": "cairo", "type": "Location", "startIndex": 17, "endIndex": 21, "score": 0.6171875 }
+        ]
+    }"#;
+
+    const V3_RESPONSE: &str = r#"{
+        "query": "turn the lights off in the kitchen",
+        "prediction": {
+            "topIntent": "HomeAutomation.TurnOff",
+            "intents": {
+                "HomeAutomation.TurnOn": { "score": 0.0126 },
+                "HomeAutomation.TurnOff": { "score": 0.969448864 },
+                "None": { "score": 0.00317 }
+            },
+            "entities": {
+                "HomeAutomation.Location": [ "kitchen" ],
+                "$instance": {
+                    "HomeAutomation.Location": [
+                        {
+                            "type": "HomeAutomation.Location",
+                            "text": "kitchen",
+                            "startIndex": 27,
+                            "length": 7,
+                            "score": 0.94,
+                            "modelTypeId": 1,
+                            "modelType": "Entity Extractor",
+                            "recognitionSources": [ "model" ]
+                        }
+                    ]
+                }
+            }
+        }
+    }"#;
+
+    fn entity(start_index: usize, end_index: usize) -> Entity {
+        Entity {
+            entity: String::new(),
+            entity_type: String::new(),
+            start_index,
+            end_index,
+            score: None,
+        }
+    }
+
+    #[test]
+    fn parses_v2_response() {
+        let result = LanguageUnderstandingResult::from_json(V2_RESPONSE).unwrap();
+        assert_eq!(result.top_intent().unwrap().intent, "BookFlight");
+        assert_eq!(result.intents.len(), 3);
+        assert_eq!(result.entities.len(), 1);
+        let location = &result.entities[0];
+        assert_eq!(location.entity_type, "Location");
+        assert_eq!(location.text(&result.query), Some("Cairo"));
+    }
+
+    #[test]
+    fn parses_v3_response() {
+        let result = LanguageUnderstandingResult::from_json(V3_RESPONSE).unwrap();
+        let top = result.top_intent().unwrap();
+        assert_eq!(top.intent, "HomeAutomation.TurnOff");
+        assert_eq!(top.score, Some(0.969448864));
+        let intents: Vec<_> = result.intents.iter().map(|x| x.intent.as_str()).collect();
+        assert_eq!(intents, ["HomeAutomation.TurnOff", "HomeAutomation.TurnOn", "None"]);
+        assert_eq!(result.entities, vec![Entity {
+            entity: "kitchen".into(),
+            entity_type: "HomeAutomation.Location".into(),
+            start_index: 27,
+            end_index: 33,
+            score: Some(0.94),
+        }]);
+        assert_eq!(result.entities[0].text(&result.query), Some("kitchen"));
+    }
+
+    #[test]
+    fn top_intent_of_empty_intents() {
+        let result = LanguageUnderstandingResult::from_json(
+            r#"{ "query": "hello", "intents": [], "entities": [] }"#).unwrap();
+        assert_eq!(result.top_intent(), None);
+
+        let result = LanguageUnderstandingResult::from_json(
+            r#"{ "query": "hello", "prediction": { "intents": {}, "entities": {} } }"#).unwrap();
+        assert_eq!(result.top_intent(), None);
+        assert!(result.entities.is_empty());
+    }
+
+    #[test]
+    fn top_intent_falls_back_to_highest_score() {
+        let result = LanguageUnderstandingResult::from_json(r#"{
+            "query": "hello",
+            "intents": [ { "intent": "None", "score": 0.2 }, { "intent": "Greet", "score": 0.7 } ]
+        }"#).unwrap();
+        assert_eq!(result.top_intent().unwrap().intent, "Greet");
+    }
+
+    #[test]
+    fn entity_text_of_non_ascii_query() {
+        let result = LanguageUnderstandingResult::from_json(r#"{
+            "query": "我想订一张去北京的机票",
+            "topScoringIntent": { "intent": "BookFlight", "score": 0.93 },
+            "entities": [ { "entity": "北京", "type": "Location", "startIndex": 6, "endIndex": 7 } ]
+        }"#).unwrap();
+        assert_eq!(result.entities[0].text(&result.query), Some("北京"));
+
+        let query = "fly to Zürich";
+        assert_eq!(entity(7, 12).text(query), Some("Zürich"));
+        assert_eq!(entity(0, 12).text(query), Some(query));
+    }
+
+    #[test]
+    fn entity_text_counts_utf16_code_units() {
+        let query = "🎵 play Beethoven";
+        assert_eq!(entity(8, 16).text(query), Some("Beethoven"));
+        assert_eq!(entity(0, 1).text(query), Some("🎵"));
+        // inside the surrogate pair of the emoji
+        assert_eq!(entity(1, 3).text(query), None);
+        assert_eq!(entity(0, 0).text(query), None);
+    }
+
+    #[test]
+    fn entity_text_out_of_range() {
+        let query = "book a flight to Cairo";
+        assert_eq!(entity(17, 22).text(query), None);
+        assert_eq!(entity(30, 31).text(query), None);
+        assert_eq!(entity(5, 4).text(query), None);
+        assert_eq!(entity(0, usize::MAX).text(query), None);
+        assert_eq!(entity(0, 0).text(""), None);
+    }
+}
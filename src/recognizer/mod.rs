@@ -1,6 +1,8 @@
+use std::any::Any;
 use std::ffi::c_void;
 use std::ops::Deref;
 use std::os::raw::c_char;
+use std::ptr;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -10,17 +12,34 @@ use num::FromPrimitive;
 use crate::{AsyncHandle, AsyncResultHandle, convert_err, ResultHandleSupport};
 use crate::async_handle::AsyncStart;
 use crate::FromHandle;
+use crate::PropertyBag;
 use crate::recognizer::events::EventFactory;
+use crate::recognizer::events::RecognitionEvent;
 use crate::recognizer::events::SessionEvent;
 use crate::ResultReason;
 use crate::SmartHandle;
 use crate::speech_api::*;
 use crate::SpxError;
-
+use crate::SPXHANDLE_INVALID;
+
+pub use self::connection::*;
+pub use self::grammar::*;
+pub use self::intent::*;
+pub use self::keyword::*;
+pub use self::language_understanding::*;
+pub use self::session::*;
 pub use self::speech::*;
+pub use self::translation::*;
 
 pub mod events;
+mod connection;
+mod grammar;
+mod intent;
+mod keyword;
+mod language_understanding;
+mod session;
 mod speech;
+mod translation;
 
 const DEFAULT_CH_BUFF_SIZE: usize = 5;
 
@@ -33,6 +52,10 @@ pub trait Recognizer: Send + Sync {
     fn enable(&mut self) -> Result<(), SpxError>;
     fn disable(&mut self) -> Result<(), SpxError>;
     fn get_handle(&self) -> SPXRECOHANDLE;
+    fn phrase_list_grammar(&self) -> Result<PhraseListGrammar, SpxError>;
+    fn connection(&self) -> Result<Connection, SpxError>;
+    fn session(&self) -> Result<Session, SpxError>;
+    fn properties(&self) -> Result<PropertyBag, SpxError>;
 }
 
 pub trait AsyncRecognizer<R, E, C>: Deref<Target=dyn Recognizer> {
@@ -40,12 +63,17 @@ pub trait AsyncRecognizer<R, E, C>: Deref<Target=dyn Recognizer> {
     fn stop_continuous_recognition(&mut self) -> Result<AsyncHandle<StopContinuousRecognitionAsyncStart>, SpxError>;
     fn recognize_once_async(&mut self) -> Result<AsyncResultHandle<RecognizeOnceAsyncStart, R>, SpxError>
         where R: ResultHandleSupport;
+    fn start_keyword_recognition(&mut self, model: &KeywordRecognitionModel)
+                                 -> Result<AsyncHandle<StartKeywordRecognitionAsyncStart>, SpxError>;
+    fn stop_keyword_recognition(&mut self) -> Result<AsyncHandle<StopKeywordRecognitionAsyncStart>, SpxError>;
 
     fn set_recognizing_channel(&mut self, v: Option<Box<Sender<E>>>);
     fn set_recognized_channel(&mut self, v: Option<Box<Sender<E>>>);
     fn set_session_started_channel(&mut self, v: Option<Box<Sender<SessionEvent>>>);
     fn set_session_stopped_channel(&mut self, v: Option<Box<Sender<SessionEvent>>>);
     fn set_canceled_channel(&mut self, v: Option<Box<Sender<C>>>);
+    fn set_speech_start_detected_channel(&mut self, v: Option<Box<Sender<RecognitionEvent>>>);
+    fn set_speech_end_detected_channel(&mut self, v: Option<Box<Sender<RecognitionEvent>>>);
 
     fn connect_recognizing(&mut self, buff_size: Option<usize>) -> Receiver<E> {
         let (s, r) = channel(buff_size.unwrap_or(DEFAULT_CH_BUFF_SIZE));
@@ -76,6 +104,18 @@ pub trait AsyncRecognizer<R, E, C>: Deref<Target=dyn Recognizer> {
         self.set_canceled_channel(Some(Box::new(s)));
         return r;
     }
+
+    fn connect_speech_start_detected(&mut self, buff_size: Option<usize>) -> Receiver<RecognitionEvent> {
+        let (s, r) = channel(buff_size.unwrap_or(DEFAULT_CH_BUFF_SIZE));
+        self.set_speech_start_detected_channel(Some(Box::new(s)));
+        r
+    }
+
+    fn connect_speech_end_detected(&mut self, buff_size: Option<usize>) -> Receiver<RecognitionEvent> {
+        let (s, r) = channel(buff_size.unwrap_or(DEFAULT_CH_BUFF_SIZE));
+        self.set_speech_end_detected_channel(Some(Box::new(s)));
+        r
+    }
 }
 
 struct BaseRecognizer {
@@ -117,6 +157,22 @@ impl Recognizer for BaseRecognizer {
     fn get_handle(&self) -> SPXRECOHANDLE {
         self.handle.get()
     }
+
+    fn phrase_list_grammar(&self) -> Result<PhraseListGrammar, SpxError> {
+        PhraseListGrammar::from_recognizer_handle(self.get_handle())
+    }
+
+    fn connection(&self) -> Result<Connection, SpxError> {
+        Connection::from_recognizer_handle(self.get_handle())
+    }
+
+    fn session(&self) -> Result<Session, SpxError> {
+        Session::from_recognizer_handle(self.get_handle())
+    }
+
+    fn properties(&self) -> Result<PropertyBag, SpxError> {
+        PropertyBag::create(self.get_handle(), recognizer_get_property_bag)
+    }
 }
 
 struct AbstractAsyncRecognizer<E, C> {
@@ -126,17 +182,32 @@ struct AbstractAsyncRecognizer<E, C> {
     session_started_sender: Option<Box<Sender<SessionEvent>>>,
     session_stopped_sender: Option<Box<Sender<SessionEvent>>>,
     canceled_sender: Option<Box<Sender<C>>>,
+    speech_start_detected_sender: Option<Box<Sender<RecognitionEvent>>>,
+    speech_end_detected_sender: Option<Box<Sender<RecognitionEvent>>>,
+    extra_channels: Vec<ExtraChannel>,
+}
+
+type SetCallbackFn = unsafe extern "C" fn(SPXRECOHANDLE, PRECOGNITION_CALLBACK_FUNC, *mut c_void) -> SPXHR;
+
+/// Sender of an event only some recognizers have, wired along with the common ones.
+struct ExtraChannel {
+    set_fn: SetCallbackFn,
+    callback: PRECOGNITION_CALLBACK_FUNC,
+    sender: Box<dyn Any + Send>,
+}
+
+impl ExtraChannel {
+    #[inline]
+    fn context(&self) -> *mut c_void {
+        &*self.sender as *const (dyn Any + Send) as *mut c_void
+    }
 }
 
 impl<R, E, C> AsyncRecognizer<R, E, C> for AbstractAsyncRecognizer<E, C>
     where E: EventFactory, C: EventFactory {
     fn start_continuous_recognition(&mut self)
                                     -> Result<AsyncHandle<StartContinuousRecognitionAsyncStart>, SpxError> {
-        self.set_callback(&self.canceled_sender, recognizer_canceled_set_callback)?;
-        self.set_callback(&self.session_started_sender, recognizer_session_started_set_callback)?;
-        self.set_callback(&self.session_stopped_sender, recognizer_session_stopped_set_callback)?;
-        self.set_callback(&self.recognizing_sender, recognizer_recognizing_set_callback)?;
-        self.set_callback(&self.recognized_sender, recognizer_recognized_set_callback)?;
+        self.set_callbacks()?;
         AsyncHandle::create(
             StartContinuousRecognitionAsyncStart(self.get_handle()),
             recognizer_async_handle_release,
@@ -161,6 +232,24 @@ impl<R, E, C> AsyncRecognizer<R, E, C> for AbstractAsyncRecognizer<E, C>
         )
     }
 
+    fn start_keyword_recognition(&mut self, model: &KeywordRecognitionModel)
+                                 -> Result<AsyncHandle<StartKeywordRecognitionAsyncStart>, SpxError> {
+        self.set_callbacks()?;
+        AsyncHandle::create(
+            StartKeywordRecognitionAsyncStart(self.get_handle(), model.clone()),
+            recognizer_async_handle_release,
+            recognizer_start_keyword_recognition_async_wait_for,
+        )
+    }
+
+    fn stop_keyword_recognition(&mut self) -> Result<AsyncHandle<StopKeywordRecognitionAsyncStart>, SpxError> {
+        AsyncHandle::create(
+            StopKeywordRecognitionAsyncStart(self.get_handle()),
+            recognizer_async_handle_release,
+            recognizer_stop_keyword_recognition_async_wait_for,
+        )
+    }
+
     fn set_recognizing_channel(&mut self, v: Option<Box<Sender<E>>>) {
         self.recognizing_sender = v;
     }
@@ -180,6 +269,40 @@ impl<R, E, C> AsyncRecognizer<R, E, C> for AbstractAsyncRecognizer<E, C>
     fn set_canceled_channel(&mut self, v: Option<Box<Sender<C>>>) {
         self.canceled_sender = v;
     }
+
+    fn set_speech_start_detected_channel(&mut self, v: Option<Box<Sender<RecognitionEvent>>>) {
+        self.speech_start_detected_sender = v;
+    }
+
+    fn set_speech_end_detected_channel(&mut self, v: Option<Box<Sender<RecognitionEvent>>>) {
+        self.speech_end_detected_sender = v;
+    }
+}
+
+/// Implementation of `BlockingRecognizer`, shared by all recognizers.
+impl<E, C> AbstractAsyncRecognizer<E, C>
+    where E: EventFactory, C: EventFactory {
+    fn recognize_once_blocking<R>(&mut self) -> Result<R, SpxError>
+        where R: FromHandle<SPXRESULTHANDLE, SpxError> {
+        let mut handle = SPXHANDLE_INVALID;
+        unsafe {
+            convert_err(recognizer_recognize_once(self.get_handle(), &mut handle))?;
+        }
+        R::from_handle(handle)
+    }
+
+    fn start_continuous_recognition_blocking(&mut self) -> Result<(), SpxError> {
+        self.set_callbacks()?;
+        unsafe {
+            convert_err(recognizer_start_continuous_recognition(self.get_handle()))
+        }
+    }
+
+    fn stop_continuous_recognition_blocking(&mut self) -> Result<(), SpxError> {
+        unsafe {
+            convert_err(recognizer_stop_continuous_recognition(self.get_handle()))
+        }
+    }
 }
 
 impl<E, C> Deref for AbstractAsyncRecognizer<E, C> {
@@ -199,9 +322,42 @@ impl<E, C> AbstractAsyncRecognizer<E, C> {
             session_started_sender: None,
             session_stopped_sender: None,
             canceled_sender: None,
+            speech_start_detected_sender: None,
+            speech_end_detected_sender: None,
+            extra_channels: Vec::new(),
         })
     }
 
+    /// Like the `set_*_channel` methods, the sender is wired when recognition starts.
+    fn set_extra_channel<T>(&mut self, set_fn: SetCallbackFn, sender: Option<Box<Sender<T>>>)
+        where T: EventFactory + Send + 'static {
+        self.extra_channels.retain(|x| x.set_fn as usize != set_fn as usize);
+        if let Some(sender) = sender {
+            self.extra_channels.push(ExtraChannel {
+                set_fn,
+                callback: Some(Self::cb_send::<T>),
+                sender,
+            });
+        }
+    }
+
+    fn set_callbacks(&self) -> Result<(), SpxError>
+        where E: EventFactory, C: EventFactory {
+        self.set_callback(&self.canceled_sender, recognizer_canceled_set_callback)?;
+        self.set_callback(&self.session_started_sender, recognizer_session_started_set_callback)?;
+        self.set_callback(&self.session_stopped_sender, recognizer_session_stopped_set_callback)?;
+        self.set_callback(&self.recognizing_sender, recognizer_recognizing_set_callback)?;
+        self.set_callback(&self.recognized_sender, recognizer_recognized_set_callback)?;
+        self.set_callback(&self.speech_start_detected_sender, recognizer_speech_start_detected_set_callback)?;
+        self.set_callback(&self.speech_end_detected_sender, recognizer_speech_end_detected_set_callback)?;
+        for x in self.extra_channels.iter() {
+            unsafe {
+                convert_err((x.set_fn)(self.get_handle(), x.callback, x.context()))?;
+            }
+        }
+        Ok(())
+    }
+
     #[inline]
     fn set_callback<T>(&self,
                        sender: &Option<Box<Sender<T>>>,
@@ -242,6 +398,17 @@ impl<E, C> AbstractAsyncRecognizer<E, C> {
     }
 }
 
+impl<E, C> Drop for AbstractAsyncRecognizer<E, C> {
+    fn drop(&mut self) {
+        for x in self.extra_channels.iter() {
+            let hr = unsafe { (x.set_fn)(self.get_handle(), None, ptr::null_mut()) };
+            if let Err(e) = convert_err(hr) {
+                error!("can not disconnect callback, err: {}", e);
+            }
+        }
+    }
+}
+
 pub struct RecognizeOnceAsyncStart(SPXRECOHANDLE);
 
 impl AsyncStart for RecognizeOnceAsyncStart {
@@ -278,6 +445,30 @@ impl AsyncStart for StopContinuousRecognitionAsyncStart {
     }
 }
 
+pub struct StartKeywordRecognitionAsyncStart(SPXRECOHANDLE, KeywordRecognitionModel);
+
+impl AsyncStart for StartKeywordRecognitionAsyncStart {
+    fn name() -> &'static str {
+        "StartKeywordRecognitionAsyncHandle"
+    }
+
+    unsafe fn async_start(&self, hasync: &mut SPXASYNCHANDLE) -> SPXHR {
+        recognizer_start_keyword_recognition_async(self.0, self.1.get_handle(), hasync)
+    }
+}
+
+pub struct StopKeywordRecognitionAsyncStart(SPXRECOHANDLE);
+
+impl AsyncStart for StopKeywordRecognitionAsyncStart {
+    fn name() -> &'static str {
+        "StopKeywordRecognitionAsyncHandle"
+    }
+
+    unsafe fn async_start(&self, hasync: &mut SPXASYNCHANDLE) -> SPXHR {
+        recognizer_stop_keyword_recognition_async(self.0, hasync)
+    }
+}
+
 pub struct RecognitionResult {
     handle: Arc<SmartHandle<SPXRESULTHANDLE>>,
 }
@@ -334,13 +525,16 @@ impl FromHandle<Arc<SmartHandle<SPXRESULTHANDLE>>, SpxError> for RecognitionResu
     }
 }
 
+/// Result handles are shared by an event and the results taken from it.
+#[inline]
+pub(crate)
+fn shared_result_handle(name: &'static str, handle: SPXRESULTHANDLE) -> Arc<SmartHandle<SPXRESULTHANDLE>> {
+    Arc::new(SmartHandle::create(name, handle, recognizer_result_handle_release))
+}
+
 impl FromHandle<SPXRESULTHANDLE, SpxError> for RecognitionResult {
     fn from_handle(handle: SPXRESULTHANDLE) -> Result<RecognitionResult, SpxError> {
-        RecognitionResult::create(Arc::new(SmartHandle::create(
-            "RecognitionResult",
-            handle,
-            RecognitionResult::release_fn(),
-        )))
+        RecognitionResult::create(shared_result_handle("RecognitionResult", handle))
     }
 }
 
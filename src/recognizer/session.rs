@@ -0,0 +1,53 @@
+use std::ops::Deref;
+use std::ops::DerefMut;
+
+use crate::convert_err;
+use crate::PropertyBag;
+use crate::PropertyId;
+use crate::SmartHandle;
+use crate::speech_api::*;
+use crate::SpxError;
+use crate::SPXHANDLE_INVALID;
+
+/// Recognition session of a recognizer, gives access to the effective session properties.
+#[derive(Debug)]
+pub struct Session {
+    handle: SmartHandle<SPXSESSIONHANDLE>,
+    props: PropertyBag,
+}
+
+impl Session {
+    pub(crate)
+    fn from_recognizer_handle(hreco: SPXRECOHANDLE) -> Result<Session, SpxError> {
+        let mut handle = SPXHANDLE_INVALID;
+        unsafe {
+            convert_err(session_from_recognizer(hreco, &mut handle))?;
+        }
+        let handle = SmartHandle::create("Session", handle, session_handle_release);
+        let props = PropertyBag::create(handle.get(), session_get_property_bag)?;
+        Ok(Session { handle, props })
+    }
+
+    #[inline]
+    pub fn get_handle(&self) -> SPXSESSIONHANDLE {
+        self.handle.get()
+    }
+
+    pub fn session_id(&self) -> Result<Option<String>, SpxError> {
+        self.get(PropertyId::SpeechSessionId)
+    }
+}
+
+impl Deref for Session {
+    type Target = PropertyBag;
+
+    fn deref(&self) -> &Self::Target {
+        &self.props
+    }
+}
+
+impl DerefMut for Session {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.props
+    }
+}
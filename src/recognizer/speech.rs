@@ -3,6 +3,7 @@ use std::ops::Deref;
 use std::ops::DerefMut;
 
 use crate::audio::AudioConfig;
+use crate::BlockingRecognizer;
 use crate::convert_err;
 use crate::recognizer::AbstractAsyncRecognizer;
 use crate::recognizer::AsyncRecognizer;
@@ -61,3 +62,17 @@ impl<CFG> DerefMut for SpeechRecognizer<CFG> {
         &mut self.base
     }
 }
+
+impl<CFG> BlockingRecognizer<R> for SpeechRecognizer<CFG> {
+    fn recognize_once_blocking(&mut self) -> Result<R, SpxError> {
+        self.base.recognize_once_blocking()
+    }
+
+    fn start_continuous_recognition_blocking(&mut self) -> Result<(), SpxError> {
+        self.base.start_continuous_recognition_blocking()
+    }
+
+    fn stop_continuous_recognition_blocking(&mut self) -> Result<(), SpxError> {
+        self.base.stop_continuous_recognition_blocking()
+    }
+}
@@ -0,0 +1,430 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::mem;
+use std::ops::Deref;
+use std::ops::DerefMut;
+use std::os::raw::c_char;
+use std::ptr;
+use std::sync::Arc;
+
+use futures::sync::mpsc::{channel, Receiver};
+use num::FromPrimitive;
+
+use crate::audio::AudioConfig;
+use crate::BlockingRecognizer;
+use crate::convert_err;
+use crate::FromHandle;
+use crate::recognizer::AbstractAsyncRecognizer;
+use crate::recognizer::AsyncRecognizer;
+use crate::recognizer::events::RecognitionCanceledEvent;
+use crate::recognizer::events::RecognitionResultEvent;
+use crate::recognizer::RecognitionResult;
+use crate::recognizer::Recognizer;
+use crate::recognizer::shared_result_handle;
+use crate::recognizer::DEFAULT_CH_BUFF_SIZE;
+use crate::recognizer::RESULT_ID_SIZE;
+use crate::ResultHandleSupport;
+use crate::ResultReason;
+use crate::SmartHandle;
+use crate::speech_api::*;
+use crate::SpeechConfig;
+use crate::SpxError;
+use crate::SPXHANDLE_INVALID;
+
+const SPXERR_BUFFER_TOO_SMALL: SPXHR = 0x19;
+
+type R = TranslationRecognitionResult;
+type E = RecognitionResultEvent<R>;
+type C = RecognitionCanceledEvent;
+
+pub struct TranslationRecognizer<CFG> {
+    base: AbstractAsyncRecognizer<E, C>,
+    #[allow(unused)]
+    config: CFG,
+    #[allow(unused)]
+    audio: Option<AudioConfig>,
+}
+
+impl<CFG> TranslationRecognizer<CFG>
+    where CFG: Borrow<SpeechConfig> {
+    pub fn from_config(config: CFG, audio: Option<AudioConfig>) -> Result<TranslationRecognizer<CFG>, SpxError> {
+        let mut handle = SPXHANDLE_INVALID;
+        unsafe {
+            convert_err(
+                recognizer_create_translation_recognizer_from_config(
+                    &mut handle,
+                    config.borrow().get_handle(),
+                    audio.as_ref().map(|c| c.get_handle()).unwrap_or(0 as SPXAUDIOCONFIGHANDLE),
+                )
+            )?;
+        }
+        Ok(TranslationRecognizer {
+            base: AbstractAsyncRecognizer::create(handle)?,
+            config,
+            audio,
+        })
+    }
+}
+
+impl<CFG> TranslationRecognizer<CFG> {
+    /// Synthesized audio of the translations, requires a voice to be set on the config.
+    /// Each result carries a chunk of audio, the last one of an utterance has an empty chunk
+    /// and `is_completed() == true`.
+    pub fn connect_synthesizing(&mut self, buff_size: Option<usize>)
+                                -> Receiver<RecognitionResultEvent<TranslationSynthesisResult>> {
+        let (s, r) = channel(buff_size.unwrap_or(DEFAULT_CH_BUFF_SIZE));
+        self.base.set_extra_channel(translator_synthesizing_audio_set_callback, Some(Box::new(s)));
+        r
+    }
+}
+
+impl<CFG> Deref for TranslationRecognizer<CFG> {
+    type Target = dyn AsyncRecognizer<R, E, C, Target=dyn Recognizer>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl<CFG> DerefMut for TranslationRecognizer<CFG> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.base
+    }
+}
+
+impl<CFG> BlockingRecognizer<R> for TranslationRecognizer<CFG> {
+    fn recognize_once_blocking(&mut self) -> Result<R, SpxError> {
+        self.base.recognize_once_blocking()
+    }
+
+    fn start_continuous_recognition_blocking(&mut self) -> Result<(), SpxError> {
+        self.base.start_continuous_recognition_blocking()
+    }
+
+    fn stop_continuous_recognition_blocking(&mut self) -> Result<(), SpxError> {
+        self.base.stop_continuous_recognition_blocking()
+    }
+}
+
+// TranslationRecognitionResult
+
+pub struct TranslationRecognitionResult {
+    base: RecognitionResult,
+}
+
+impl TranslationRecognitionResult {
+    fn create(handle: Arc<SmartHandle<SPXRESULTHANDLE>>) -> Result<TranslationRecognitionResult, SpxError> {
+        Ok(TranslationRecognitionResult {
+            base: RecognitionResult::create(handle)?,
+        })
+    }
+
+    /// Target language -> translated text.
+    pub fn translations(&self) -> Result<HashMap<String, String>, SpxError> {
+        let mut len: usize = 0;
+        let hr = unsafe {
+            translation_text_result_get_translation_text_buffer_header(self.get_handle(), ptr::null_mut(), &mut len)
+        };
+        if hr != SPXERR_BUFFER_TOO_SMALL {
+            // nothing has been translated
+            convert_err(hr)?;
+            return Ok(HashMap::new());
+        }
+        let buff = TranslationTextBuffer::populate(self.get_handle(), len)?;
+        buff.entries()
+    }
+}
+
+impl Deref for TranslationRecognitionResult {
+    type Target = RecognitionResult;
+
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl FromHandle<Arc<SmartHandle<SPXRESULTHANDLE>>, SpxError> for TranslationRecognitionResult {
+    fn from_handle(handle: Arc<SmartHandle<SPXRESULTHANDLE>>) -> Result<TranslationRecognitionResult, SpxError> {
+        TranslationRecognitionResult::create(handle)
+    }
+}
+
+impl FromHandle<SPXRESULTHANDLE, SpxError> for TranslationRecognitionResult {
+    fn from_handle(handle: SPXRESULTHANDLE) -> Result<TranslationRecognitionResult, SpxError> {
+        TranslationRecognitionResult::create(shared_result_handle("TranslationRecognitionResult", handle))
+    }
+}
+
+impl ResultHandleSupport for TranslationRecognitionResult {
+    fn async_wait_fn() -> unsafe extern "C" fn(SPXASYNCHANDLE, u32, *mut SPXRESULTHANDLE) -> SPXHR {
+        recognizer_recognize_once_async_wait_for
+    }
+
+    fn release_fn() -> unsafe extern "C" fn(SPXRESULTHANDLE) -> SPXHR {
+        recognizer_result_handle_release
+    }
+}
+
+unsafe impl Sync for TranslationRecognitionResult {}
+
+unsafe impl Send for TranslationRecognitionResult {}
+
+// TranslationSynthesisResult
+
+pub struct TranslationSynthesisResult {
+    handle: Arc<SmartHandle<SPXRESULTHANDLE>>,
+}
+
+impl TranslationSynthesisResult {
+    #[inline(always)]
+    pub fn get_handle(&self) -> SPXRESULTHANDLE {
+        self.handle.get()
+    }
+
+    /// Id of the recognition result the audio belongs to.
+    pub fn id(&self) -> Result<String, SpxError> {
+        crate::spx_populate_string(self.get_handle(), RESULT_ID_SIZE, result_get_result_id)
+    }
+
+    pub fn reason(&self) -> Result<ResultReason, SpxError> {
+        let code = crate::spx_populate(self.get_handle(), result_get_reason)?;
+        Ok(ResultReason::from_u32(code).expect("unknown reason"))
+    }
+
+    pub fn is_completed(&self) -> Result<bool, SpxError> {
+        Ok(self.reason()? == ResultReason::SynthesizingAudioCompleted)
+    }
+
+    pub fn audio_data(&self) -> Result<Vec<u8>, SpxError> {
+        let mut len: usize = 0;
+        let hr = unsafe {
+            translation_synthesis_result_get_audio_data(self.get_handle(), ptr::null_mut(), &mut len)
+        };
+        if hr != SPXERR_BUFFER_TOO_SMALL {
+            convert_err(hr)?;
+            return Ok(Vec::new());
+        }
+        let mut buff = vec![0u8; len];
+        unsafe {
+            convert_err(translation_synthesis_result_get_audio_data(self.get_handle(), buff.as_mut_ptr(), &mut len))?;
+        }
+        buff.truncate(len);
+        Ok(buff)
+    }
+}
+
+impl FromHandle<Arc<SmartHandle<SPXRESULTHANDLE>>, SpxError> for TranslationSynthesisResult {
+    fn from_handle(handle: Arc<SmartHandle<SPXRESULTHANDLE>>) -> Result<TranslationSynthesisResult, SpxError> {
+        Ok(TranslationSynthesisResult { handle })
+    }
+}
+
+unsafe impl Sync for TranslationSynthesisResult {}
+
+unsafe impl Send for TranslationSynthesisResult {}
+
+// TranslationTextBuffer
+
+/// Owned copy of a `Result_TranslationTextBufferHeader` followed by its data.
+/// Every pointer in the header is checked against the buffer bounds before it is followed.
+struct TranslationTextBuffer {
+    // Vec<usize> keeps the header properly aligned
+    data: Vec<usize>,
+    len: usize,
+}
+
+impl TranslationTextBuffer {
+    fn populate(handle: SPXRESULTHANDLE, len: usize) -> Result<TranslationTextBuffer, SpxError> {
+        if len < mem::size_of::<Result_TranslationTextBufferHeader>() {
+            return Err(SpxError::InvalidResultBuffer);
+        }
+        let word_size = mem::size_of::<usize>();
+        let mut data = vec![0usize; (len + word_size - 1) / word_size];
+        let mut filled = len;
+        unsafe {
+            convert_err(translation_text_result_get_translation_text_buffer_header(
+                handle,
+                data.as_mut_ptr() as *mut Result_TranslationTextBufferHeader,
+                &mut filled,
+            ))?;
+        }
+        if filled > len {
+            return Err(SpxError::InvalidResultBuffer);
+        }
+        TranslationTextBuffer::from_data(data, filled)
+    }
+
+    /// The first `len` bytes of `data` have been filled by the Speech SDK.
+    fn from_data(data: Vec<usize>, len: usize) -> Result<TranslationTextBuffer, SpxError> {
+        if len < mem::size_of::<Result_TranslationTextBufferHeader>() || len > data.len() * mem::size_of::<usize>() {
+            return Err(SpxError::InvalidResultBuffer);
+        }
+        let result = TranslationTextBuffer { data, len };
+        if result.header().bufferSize > result.len {
+            return Err(SpxError::InvalidResultBuffer);
+        }
+        Ok(result)
+    }
+
+    #[inline]
+    fn header(&self) -> &Result_TranslationTextBufferHeader {
+        unsafe { &*(self.data.as_ptr() as *const Result_TranslationTextBufferHeader) }
+    }
+
+    #[inline]
+    fn start(&self) -> usize {
+        self.data.as_ptr() as usize
+    }
+
+    #[inline]
+    fn contains(&self, addr: usize, size: usize) -> bool {
+        addr >= self.start() && addr.checked_add(size).map_or(false, |end| end <= self.start() + self.len)
+    }
+
+    fn entries(&self) -> Result<HashMap<String, String>, SpxError> {
+        let header = self.header();
+        let count = header.numberEntries;
+        let mut result = HashMap::with_capacity(count);
+        for i in 0..count {
+            let lang = self.string_at(header.targetLanguages, i)?;
+            let text = self.string_at(header.translationTexts, i)?;
+            result.insert(lang, text);
+        }
+        Ok(result)
+    }
+
+    fn string_at(&self, array: *mut *mut c_char, index: usize) -> Result<String, SpxError> {
+        let ptr_size = mem::size_of::<*mut c_char>();
+        let slot = index.checked_mul(ptr_size)
+            .and_then(|offset| (array as usize).checked_add(offset))
+            .ok_or(SpxError::InvalidResultBuffer)?;
+        if slot % mem::align_of::<*mut c_char>() != 0 || !self.contains(slot, ptr_size) {
+            return Err(SpxError::InvalidResultBuffer);
+        }
+        let s = unsafe { *(slot as *const *const c_char) } as usize;
+        if !self.contains(s, 1) {
+            return Err(SpxError::InvalidResultBuffer);
+        }
+        let max_len = self.start() + self.len - s;
+        let bytes = unsafe { std::slice::from_raw_parts(s as *const u8, max_len) };
+        let nul = bytes.iter().position(|&b| b == 0).ok_or(SpxError::InvalidCString)?;
+        Ok(String::from_utf8(bytes[..nul].to_vec())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORD: usize = mem::size_of::<usize>();
+    const HEADER_WORDS: usize = 4;
+
+    // header, target language pointers, translation text pointers, then the strings
+    fn buffer(entries: &[(&str, &str)]) -> (Vec<usize>, usize) {
+        let n = entries.len();
+        let strings_at = (HEADER_WORDS + 2 * n) * WORD;
+        let mut strings = Vec::new();
+        let mut offsets = Vec::new();
+        for (lang, text) in entries {
+            for s in &[lang, text] {
+                offsets.push(strings_at + strings.len());
+                strings.extend_from_slice(s.as_bytes());
+                strings.push(0);
+            }
+        }
+        let len = strings_at + strings.len();
+        let mut data = vec![0usize; (len + WORD - 1) / WORD];
+        let base = data.as_ptr() as usize;
+        data[0] = len;
+        data[1] = n;
+        if n > 0 {
+            data[2] = base + HEADER_WORDS * WORD;
+            data[3] = base + (HEADER_WORDS + n) * WORD;
+        }
+        for i in 0..n {
+            data[HEADER_WORDS + i] = base + offsets[2 * i];
+            data[HEADER_WORDS + n + i] = base + offsets[2 * i + 1];
+        }
+        let bytes = unsafe { std::slice::from_raw_parts_mut(data.as_mut_ptr() as *mut u8, data.len() * WORD) };
+        bytes[strings_at..len].copy_from_slice(&strings);
+        (data, len)
+    }
+
+    fn entries(data: Vec<usize>, len: usize) -> Result<HashMap<String, String>, SpxError> {
+        TranslationTextBuffer::from_data(data, len)?.entries()
+    }
+
+    fn assert_invalid(result: Result<HashMap<String, String>, SpxError>) {
+        match result {
+            Err(SpxError::InvalidResultBuffer) => {}
+            x => panic!("expected InvalidResultBuffer, got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn reads_translations() {
+        let (data, len) = buffer(&[("de", "Hallo Welt"), ("zh-Hans", "你好，世界")]);
+        let result = entries(data, len).unwrap();
+        assert_eq!(result.len(), 2);
+        assert_eq!(result["de"], "Hallo Welt");
+        assert_eq!(result["zh-Hans"], "你好，世界");
+    }
+
+    #[test]
+    fn reads_zero_targets() {
+        let (data, len) = buffer(&[]);
+        assert_eq!(len, HEADER_WORDS * WORD);
+        assert!(entries(data, len).unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_truncated_buffers() {
+        let (data, len) = buffer(&[("de", "Hallo")]);
+        assert!(TranslationTextBuffer::from_data(data.clone(), HEADER_WORDS * WORD - 1).is_err());
+        assert!(TranslationTextBuffer::from_data(data.clone(), data.len() * WORD + 1).is_err());
+        // shorter than the size in the header
+        assert!(TranslationTextBuffer::from_data(data.clone(), len - 1).is_err());
+
+        // the last string is not terminated within the buffer
+        let (mut data, len) = buffer(&[("de", "Hallo")]);
+        data[0] = len - 1;
+        match entries(data, len - 1) {
+            Err(SpxError::InvalidCString) => {}
+            x => panic!("expected InvalidCString, got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn rejects_out_of_range_pointers() {
+        // pointer arrays outside of the buffer, the pointers of a copy still refer to the original
+        let (data, len) = buffer(&[("de", "Hallo")]);
+        assert_invalid(entries(data.clone(), len));
+        let (mut bad, len) = buffer(&[("de", "Hallo")]);
+        bad[3] = 8;
+        assert_invalid(entries(bad, len));
+        let (mut bad, len) = buffer(&[("de", "Hallo")]);
+        bad[2] = bad.as_ptr() as usize + len;
+        assert_invalid(entries(bad, len));
+        let (mut bad, len) = buffer(&[("de", "Hallo")]);
+        bad[2] = usize::MAX - 1;
+        assert_invalid(entries(bad, len));
+
+        // misaligned pointer array
+        let (mut bad, len) = buffer(&[("de", "Hallo")]);
+        bad[2] += 1;
+        assert_invalid(entries(bad, len));
+
+        // more entries than pointers
+        let (mut bad, len) = buffer(&[("de", "Hallo")]);
+        bad[1] = 1000;
+        assert_invalid(entries(bad, len));
+
+        // string pointers outside of the buffer
+        let (mut bad, len) = buffer(&[("de", "Hallo")]);
+        bad[HEADER_WORDS] = bad.as_ptr() as usize + len;
+        assert_invalid(entries(bad, len));
+        let (mut bad, len) = buffer(&[("de", "Hallo")]);
+        bad[HEADER_WORDS + 1] = 0;
+        assert_invalid(entries(bad, len));
+    }
+}
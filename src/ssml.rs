@@ -0,0 +1,470 @@
+//! Builder of SSML documents for `SpeechSynthesizer::speak_ssml*`.
+//!
+//! All text and attribute values are escaped, nesting is checked when the document is built.
+
+use std::fmt::Write;
+use std::ops::Deref;
+use std::ops::DerefMut;
+use std::time::Duration;
+
+use crate::PropertyId;
+use crate::SpeechConfig;
+use crate::SpxError;
+
+const DEFAULT_LANGUAGE: &str = "en-US";
+
+#[derive(Debug, Clone)]
+pub struct SsmlBuilder {
+    lang: String,
+    voice: Option<String>,
+    content: Content,
+}
+
+impl SsmlBuilder {
+    pub fn new(lang: impl Into<String>) -> SsmlBuilder {
+        SsmlBuilder {
+            lang: lang.into(),
+            voice: None,
+            content: Content::default(),
+        }
+    }
+
+    /// Language and default voice are taken from the synthesis properties of the config.
+    pub fn from_config(config: &SpeechConfig) -> Result<SsmlBuilder, SpxError> {
+        let lang = config.get(PropertyId::SpeechServiceConnectionSynthLanguage)?
+            .filter(|x| !x.is_empty())
+            .unwrap_or_else(|| DEFAULT_LANGUAGE.to_owned());
+        let mut result = SsmlBuilder::new(lang);
+        result.voice = config.get(PropertyId::SpeechServiceConnectionSynthVoice)?
+            .filter(|x| !x.is_empty());
+        Ok(result)
+    }
+
+    /// Content outside of `voice` elements is spoken by this voice.
+    pub fn default_voice(&mut self, name: impl Into<String>) -> &mut Self {
+        self.voice = Some(name.into());
+        self
+    }
+
+    pub fn language(&self) -> &str {
+        &self.lang
+    }
+
+    pub fn default_voice_name(&self) -> Option<&str> {
+        self.voice.as_deref()
+    }
+
+    /// Renders the document, fails if elements are nested in a way the service does not accept.
+    pub fn build(&self) -> Result<String, SpxError> {
+        self.content.validate(if self.voice.is_some() { Scope::SpeakWithVoice } else { Scope::Speak })?;
+        let mut out = String::new();
+        write!(
+            out,
+            r#"<speak version="1.0" xmlns="http://www.w3.org/2001/10/synthesis" xml:lang="{}">"#,
+            escape_attr(&self.lang),
+        ).unwrap();
+        match self.voice {
+            Some(ref voice) => {
+                // group everything outside of explicit voice elements into the default voice
+                let mut pending: Vec<&Node> = Vec::new();
+                for node in self.content.nodes.iter() {
+                    if node.is_voice() {
+                        render_default_voice(&mut out, voice, &pending);
+                        pending.clear();
+                        node.render(&mut out);
+                    } else {
+                        pending.push(node);
+                    }
+                }
+                render_default_voice(&mut out, voice, &pending);
+            }
+            None => self.content.render(&mut out),
+        }
+        out.push_str("</speak>");
+        Ok(out)
+    }
+}
+
+impl Deref for SsmlBuilder {
+    type Target = Content;
+
+    fn deref(&self) -> &Content {
+        &self.content
+    }
+}
+
+impl DerefMut for SsmlBuilder {
+    fn deref_mut(&mut self) -> &mut Content {
+        &mut self.content
+    }
+}
+
+/// Mixed content of an element.
+#[derive(Debug, Clone, Default)]
+pub struct Content {
+    nodes: Vec<Node>,
+}
+
+impl Content {
+    pub fn text(&mut self, text: impl AsRef<str>) -> &mut Self {
+        self.nodes.push(Node::Text(text.as_ref().to_owned()));
+        self
+    }
+
+    /// Only allowed at the top level of the document.
+    pub fn voice(&mut self, name: impl AsRef<str>, f: impl FnOnce(&mut Content)) -> &mut Self {
+        self.element("voice", vec![("name", name.as_ref().to_owned())], f)
+    }
+
+    pub fn prosody(&mut self, prosody: &Prosody, f: impl FnOnce(&mut Content)) -> &mut Self {
+        let mut attrs = Vec::new();
+        if let Some(ref x) = prosody.rate {
+            attrs.push(("rate", x.clone()));
+        }
+        if let Some(ref x) = prosody.pitch {
+            attrs.push(("pitch", x.clone()));
+        }
+        if let Some(ref x) = prosody.volume {
+            attrs.push(("volume", x.clone()));
+        }
+        self.element("prosody", attrs, f)
+    }
+
+    pub fn break_time(&mut self, time: Duration) -> &mut Self {
+        let ms = time.as_secs() * 1000 + u64::from(time.subsec_millis());
+        self.empty_element("break", vec![("time", format!("{}ms", ms))])
+    }
+
+    pub fn break_strength(&mut self, strength: BreakStrength) -> &mut Self {
+        self.empty_element("break", vec![("strength", strength.as_str().to_owned())])
+    }
+
+    pub fn emphasis(&mut self, level: EmphasisLevel, f: impl FnOnce(&mut Content)) -> &mut Self {
+        self.element("emphasis", vec![("level", level.as_str().to_owned())], f)
+    }
+
+    /// `interpret_as` is e.g. `date`, `cardinal` or `characters`.
+    pub fn say_as(&mut self, interpret_as: impl AsRef<str>, format: Option<&str>, text: impl AsRef<str>) -> &mut Self {
+        let mut attrs = vec![("interpret-as", interpret_as.as_ref().to_owned())];
+        if let Some(format) = format {
+            attrs.push(("format", format.to_owned()));
+        }
+        self.text_element("say-as", attrs, text)
+    }
+
+    /// `alphabet` is e.g. `ipa` or `sapi`.
+    pub fn phoneme(&mut self, alphabet: impl AsRef<str>, ph: impl AsRef<str>, text: impl AsRef<str>) -> &mut Self {
+        let attrs = vec![("alphabet", alphabet.as_ref().to_owned()), ("ph", ph.as_ref().to_owned())];
+        self.text_element("phoneme", attrs, text)
+    }
+
+    /// `text` is written, `alias` is spoken.
+    pub fn sub(&mut self, alias: impl AsRef<str>, text: impl AsRef<str>) -> &mut Self {
+        self.text_element("sub", vec![("alias", alias.as_ref().to_owned())], text)
+    }
+
+    /// The content is spoken if the audio file can not be played.
+    pub fn audio(&mut self, src: impl AsRef<str>, f: impl FnOnce(&mut Content)) -> &mut Self {
+        self.element("audio", vec![("src", src.as_ref().to_owned())], f)
+    }
+
+    /// Switches the language of a multilingual voice, must be inside a voice.
+    pub fn lang(&mut self, lang: impl AsRef<str>, f: impl FnOnce(&mut Content)) -> &mut Self {
+        self.element("lang", vec![("xml:lang", lang.as_ref().to_owned())], f)
+    }
+
+    fn element(&mut self, name: &'static str, attrs: Vec<(&'static str, String)>, f: impl FnOnce(&mut Content)) -> &mut Self {
+        let mut content = Content::default();
+        f(&mut content);
+        self.nodes.push(Node::Element { name, attrs, content });
+        self
+    }
+
+    fn text_element(&mut self, name: &'static str, attrs: Vec<(&'static str, String)>, text: impl AsRef<str>) -> &mut Self {
+        let mut content = Content::default();
+        content.text(text);
+        self.nodes.push(Node::Element { name, attrs, content });
+        self
+    }
+
+    fn empty_element(&mut self, name: &'static str, attrs: Vec<(&'static str, String)>) -> &mut Self {
+        self.nodes.push(Node::Element { name, attrs, content: Content::default() });
+        self
+    }
+
+    fn validate(&self, scope: Scope) -> Result<(), SpxError> {
+        for node in self.nodes.iter() {
+            if let Node::Element { name, attrs, content } = node {
+                let inner = match (*name, scope) {
+                    ("voice", Scope::Speak) | ("voice", Scope::SpeakWithVoice) => Scope::Voice,
+                    ("voice", _) =>
+                        return Err(SpxError::InvalidSsml("voice must be a direct child of speak".to_owned())),
+                    ("lang", Scope::Speak) | ("lang", Scope::Inline) =>
+                        return Err(SpxError::InvalidSsml("lang must be inside a voice".to_owned())),
+                    (_, Scope::Speak) => Scope::Inline,
+                    (_, Scope::SpeakWithVoice) => Scope::Voice,
+                    (_, x) => x,
+                };
+                if let Some((attr, _)) = attrs.iter().find(|(_, v)| v.trim().is_empty()) {
+                    return Err(SpxError::InvalidSsml(format!("empty attribute {} of {}", attr, name)));
+                }
+                if *name == "prosody" && attrs.is_empty() {
+                    return Err(SpxError::InvalidSsml("prosody without any attribute".to_owned()));
+                }
+                content.validate(inner)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn render(&self, out: &mut String) {
+        for node in self.nodes.iter() {
+            node.render(out);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scope {
+    /// Top level of a document without default voice.
+    Speak,
+    /// Top level of a document with default voice.
+    SpeakWithVoice,
+    /// Inside a voice.
+    Voice,
+    /// Inside another element, outside of any voice.
+    Inline,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Text(String),
+    Element {
+        name: &'static str,
+        attrs: Vec<(&'static str, String)>,
+        content: Content,
+    },
+}
+
+impl Node {
+    fn is_voice(&self) -> bool {
+        match self {
+            Node::Element { name, .. } => *name == "voice",
+            Node::Text(_) => false,
+        }
+    }
+
+    fn render(&self, out: &mut String) {
+        match self {
+            Node::Text(text) => out.push_str(&escape_text(text)),
+            Node::Element { name, attrs, content } => {
+                out.push('<');
+                out.push_str(name);
+                for (k, v) in attrs.iter() {
+                    write!(out, r#" {}="{}""#, k, escape_attr(v)).unwrap();
+                }
+                if content.nodes.is_empty() {
+                    out.push_str("/>");
+                } else {
+                    out.push('>');
+                    content.render(out);
+                    write!(out, "</{}>", name).unwrap();
+                }
+            }
+        }
+    }
+}
+
+fn render_default_voice(out: &mut String, voice: &str, nodes: &[&Node]) {
+    if nodes.is_empty() {
+        return;
+    }
+    write!(out, r#"<voice name="{}">"#, escape_attr(voice)).unwrap();
+    for node in nodes {
+        node.render(out);
+    }
+    out.push_str("</voice>");
+}
+
+/// Values are passed through as is, e.g. `rate: Some("+10%".into())` or `pitch: Some("low".into())`.
+#[derive(Debug, Clone, Default)]
+pub struct Prosody {
+    pub rate: Option<String>,
+    pub pitch: Option<String>,
+    pub volume: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BreakStrength {
+    None,
+    XWeak,
+    Weak,
+    Medium,
+    Strong,
+    XStrong,
+}
+
+impl BreakStrength {
+    fn as_str(self) -> &'static str {
+        match self {
+            BreakStrength::None => "none",
+            BreakStrength::XWeak => "x-weak",
+            BreakStrength::Weak => "weak",
+            BreakStrength::Medium => "medium",
+            BreakStrength::Strong => "strong",
+            BreakStrength::XStrong => "x-strong",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EmphasisLevel {
+    Strong,
+    Moderate,
+    None,
+    Reduced,
+}
+
+impl EmphasisLevel {
+    fn as_str(self) -> &'static str {
+        match self {
+            EmphasisLevel::Strong => "strong",
+            EmphasisLevel::Moderate => "moderate",
+            EmphasisLevel::None => "none",
+            EmphasisLevel::Reduced => "reduced",
+        }
+    }
+}
+
+/// Escapes `&`, `<` and `>`, characters which are not allowed in XML are dropped.
+pub fn escape_text(s: &str) -> String {
+    escape(s, false)
+}
+
+/// Like `escape_text`, quotes are escaped as well.
+pub fn escape_attr(s: &str) -> String {
+    escape(s, true)
+}
+
+fn escape(s: &str, quotes: bool) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' if quotes => result.push_str("&quot;"),
+            '\'' if quotes => result.push_str("&apos;"),
+            '\t' | '\n' | '\r' => result.push(c),
+            c if (c as u32) < 0x20 || c == '\u{fffe}' || c == '\u{ffff}' => {}
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    const SPEAK: &str = r#"<speak version="1.0" xmlns="http://www.w3.org/2001/10/synthesis" xml:lang="en-US">"#;
+
+    fn invalid_ssml(builder: &SsmlBuilder) -> String {
+        match builder.build() {
+            Err(SpxError::InvalidSsml(msg)) => msg,
+            x => panic!("expected InvalidSsml, got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn escapes_text_and_attributes() {
+        assert_eq!(escape_text(r#"a & b < c > d "e" 'f'"#), r#"a &amp; b &lt; c &gt; d "e" 'f'"#);
+        assert_eq!(escape_attr(r#"a & b < c > d "e" 'f'"#), "a &amp; b &lt; c &gt; d &quot;e&quot; &apos;f&apos;");
+        assert_eq!(escape_text("a\u{1}\tb\u{fffe}"), "a\tb");
+    }
+
+    #[test]
+    fn builds_escaped_document() {
+        let mut builder = SsmlBuilder::new("en-US");
+        builder.text("Tom & <Jerry>")
+            .say_as("characters", None, "a\"b")
+            .break_time(Duration::from_millis(1500))
+            .prosody(&Prosody { rate: Some("+10%".into()), ..Prosody::default() }, |c| {
+                c.text("fast");
+            });
+        assert_eq!(
+            builder.build().unwrap(),
+            format!(
+                "{}Tom &amp; &lt;Jerry&gt;<say-as interpret-as=\"characters\">a\"b</say-as>\
+                 <break time=\"1500ms\"/><prosody rate=\"+10%\">fast</prosody></speak>",
+                SPEAK,
+            ),
+        );
+    }
+
+    #[test]
+    fn groups_content_into_default_voice() {
+        let mut builder = SsmlBuilder::new("en-US");
+        builder.default_voice("a\"b")
+            .text("x")
+            .voice("other", |c| {
+                c.text("y");
+            })
+            .text("z");
+        assert_eq!(
+            builder.build().unwrap(),
+            format!(
+                "{}<voice name=\"a&quot;b\">x</voice><voice name=\"other\">y</voice>\
+                 <voice name=\"a&quot;b\">z</voice></speak>",
+                SPEAK,
+            ),
+        );
+    }
+
+    #[test]
+    fn rejects_nested_voice() {
+        let mut builder = SsmlBuilder::new("en-US");
+        builder.voice("a", |c| {
+            c.voice("b", |c| {
+                c.text("x");
+            });
+        });
+        assert_eq!(invalid_ssml(&builder), "voice must be a direct child of speak");
+
+        let mut builder = SsmlBuilder::new("en-US");
+        builder.emphasis(EmphasisLevel::Strong, |c| {
+            c.voice("b", |_| {});
+        });
+        assert_eq!(invalid_ssml(&builder), "voice must be a direct child of speak");
+    }
+
+    #[test]
+    fn rejects_lang_outside_of_voice() {
+        let mut builder = SsmlBuilder::new("en-US");
+        builder.lang("de-DE", |c| {
+            c.text("x");
+        });
+        assert_eq!(invalid_ssml(&builder), "lang must be inside a voice");
+
+        let mut builder = SsmlBuilder::new("en-US");
+        builder.default_voice("a").lang("de-DE", |c| {
+            c.text("x");
+        });
+        assert!(builder.build().is_ok());
+    }
+
+    #[test]
+    fn rejects_empty_attributes() {
+        let mut builder = SsmlBuilder::new("en-US");
+        builder.voice(" ", |_| {});
+        assert_eq!(invalid_ssml(&builder), "empty attribute name of voice");
+
+        let mut builder = SsmlBuilder::new("en-US");
+        builder.prosody(&Prosody::default(), |c| {
+            c.text("x");
+        });
+        assert_eq!(invalid_ssml(&builder), "prosody without any attribute");
+    }
+}
@@ -1,27 +1,42 @@
 use std::borrow::Borrow;
+use std::ffi::c_void;
 use std::ffi::CString;
+use std::io::Read;
+use std::ptr;
 use std::sync::Arc;
 
+use futures::sync::mpsc::{channel, Receiver, Sender};
 use num::FromPrimitive;
 
 use crate::{
     AsyncResultHandle,
+    AudioContainer,
+    AudioFormatDescriptor,
     CancellationErrorCode,
     CancellationReason,
     convert_err,
     FfiObject,
     FromHandle,
+    PropertyBag,
+    PropertyId,
     ResultHandleSupport,
     SmartHandle,
     SpeechConfig,
+    SpeechSynthesisOutputFormat,
     SpxError,
     SPXHANDLE_INVALID,
 };
 use crate::async_handle::AsyncStart;
 use crate::audio::AudioConfig;
+use crate::audio::AudioDataStream;
+use crate::audio::AudioStreamFormat;
+use crate::audio::wav;
+use crate::recognizer::events::EventFactory;
 use crate::ResultReason;
 use crate::speech_api::*;
 
+const DEFAULT_CH_BUFF_SIZE: usize = 5;
+
 type SpeakAsyncFn = unsafe extern "C" fn(
     SPXSYNTHHANDLE,
     *const ::std::os::raw::c_char,
@@ -29,32 +44,107 @@ type SpeakAsyncFn = unsafe extern "C" fn(
     *mut SPXASYNCHANDLE,
 ) -> SPXHR;
 
+type SpeakFn = unsafe extern "C" fn(
+    SPXSYNTHHANDLE,
+    *const ::std::os::raw::c_char,
+    u32,
+    *mut SPXRESULTHANDLE,
+) -> SPXHR;
+
+type SynthesizerSetCallbackFn = unsafe extern "C" fn(SPXSYNTHHANDLE, PSYNTHESIS_CALLBACK_FUNC, *mut c_void) -> SPXHR;
+
+/// The native synthesizer together with its audio output. Pending speak handles keep both alive,
+/// the Speech SDK writes into an output stream until the native synthesizer is released.
+struct SynthesizerHandle {
+    // fields are dropped in order, the native synthesizer is released before its output
+    handle: SmartHandle<SPXSYNTHHANDLE>,
+    #[allow(unused)]
+    audio: Option<AudioConfig>,
+}
+
+impl SynthesizerHandle {
+    #[inline(always)]
+    fn get(&self) -> SPXSYNTHHANDLE {
+        self.handle.get()
+    }
+}
+
+// the native synthesizer may be used from any thread, `audio` is never accessed once it is set
+unsafe impl Send for SynthesizerHandle {}
+
+unsafe impl Sync for SynthesizerHandle {}
+
 pub struct SpeechSynthesizer {
-    handle: Arc<SmartHandle<SPXSYNTHHANDLE>>,
+    handle: Arc<SynthesizerHandle>,
+    started_sender: Option<Box<Sender<SpeechSynthesisEvent>>>,
+    synthesizing_sender: Option<Box<Sender<SpeechSynthesisEvent>>>,
+    completed_sender: Option<Box<Sender<SpeechSynthesisEvent>>>,
+    canceled_sender: Option<Box<Sender<SpeechSynthesisEvent>>>,
 }
 
 impl SpeechSynthesizer {
-    pub fn from_config(config: impl Borrow<SpeechConfig>) -> Result<SpeechSynthesizer, SpxError> {
+    /// Audio is played or written to the given output, e.g. `AudioConfig::output_from_default_speaker()`.
+    /// Without an output no device is used, the audio is only returned by `SpeechSynthesisResult::audio_data`.
+    pub fn from_config(config: impl Borrow<SpeechConfig>, audio: Option<AudioConfig>) -> Result<SpeechSynthesizer, SpxError> {
         let mut handle = SPXHANDLE_INVALID;
-        let audio_config = AudioConfig::output_from_default_speaker()?;
         unsafe {
             convert_err(
                 synthesizer_create_speech_synthesizer_from_config(
                     &mut handle,
                     config.borrow().get_handle(),
-                    audio_config.get_handle(),
+                    audio.as_ref().map(|c| c.get_handle()).unwrap_or(0 as SPXAUDIOCONFIGHANDLE),
                 )
             )?;
         }
         Ok(SpeechSynthesizer {
-            handle: Arc::new(SmartHandle::create(
-                "SpeechSynthesizer",
-                handle,
-                synthesizer_handle_release,
-            ))
+            handle: Arc::new(SynthesizerHandle {
+                handle: SmartHandle::create(
+                    "SpeechSynthesizer",
+                    handle,
+                    synthesizer_handle_release,
+                ),
+                audio,
+            }),
+            started_sender: None,
+            synthesizing_sender: None,
+            completed_sender: None,
+            canceled_sender: None,
         })
     }
 
+    pub fn connect_synthesis_started(&mut self, buff_size: Option<usize>) -> Result<Receiver<SpeechSynthesisEvent>, SpxError> {
+        let (s, r) = channel(buff_size.unwrap_or(DEFAULT_CH_BUFF_SIZE));
+        let sender = Some(Box::new(s));
+        self.set_callback(&sender, synthesizer_started_set_callback)?;
+        self.started_sender = sender;
+        Ok(r)
+    }
+
+    /// Each event carries the audio chunk synthesized since the previous one.
+    pub fn connect_synthesizing(&mut self, buff_size: Option<usize>) -> Result<Receiver<SpeechSynthesisEvent>, SpxError> {
+        let (s, r) = channel(buff_size.unwrap_or(DEFAULT_CH_BUFF_SIZE));
+        let sender = Some(Box::new(s));
+        self.set_callback(&sender, synthesizer_synthesizing_set_callback)?;
+        self.synthesizing_sender = sender;
+        Ok(r)
+    }
+
+    pub fn connect_synthesis_completed(&mut self, buff_size: Option<usize>) -> Result<Receiver<SpeechSynthesisEvent>, SpxError> {
+        let (s, r) = channel(buff_size.unwrap_or(DEFAULT_CH_BUFF_SIZE));
+        let sender = Some(Box::new(s));
+        self.set_callback(&sender, synthesizer_completed_set_callback)?;
+        self.completed_sender = sender;
+        Ok(r)
+    }
+
+    pub fn connect_synthesis_canceled(&mut self, buff_size: Option<usize>) -> Result<Receiver<SpeechSynthesisEvent>, SpxError> {
+        let (s, r) = channel(buff_size.unwrap_or(DEFAULT_CH_BUFF_SIZE));
+        let sender = Some(Box::new(s));
+        self.set_callback(&sender, synthesizer_canceled_set_callback)?;
+        self.canceled_sender = sender;
+        Ok(r)
+    }
+
     pub fn speak_text_async(&self, text: impl AsRef<str>)
                             -> Result<AsyncResultHandle<SpeakAsyncStart, SpeechSynthesisResult>, SpxError> {
         self.speak_async(text, synthesizer_speak_text_async)
@@ -65,6 +155,50 @@ impl SpeechSynthesizer {
         self.speak_async(text, synthesizer_speak_ssml_async)
     }
 
+    /// Resolves as soon as synthesis has started, with a `ResultReason::SynthesizingAudioStart` result.
+    /// The rest of the audio can be read incrementally from `SpeechSynthesisResult::audio_data_stream`.
+    pub fn start_speaking_text_async(&self, text: impl AsRef<str>)
+                                     -> Result<AsyncResultHandle<SpeakAsyncStart, SpeechSynthesisResult>, SpxError> {
+        self.speak_async(text, synthesizer_start_speaking_text_async)
+    }
+
+    /// SSML variant of `start_speaking_text_async`.
+    pub fn start_speaking_ssml_async(&self, text: impl AsRef<str>)
+                                     -> Result<AsyncResultHandle<SpeakAsyncStart, SpeechSynthesisResult>, SpxError> {
+        self.speak_async(text, synthesizer_start_speaking_ssml_async)
+    }
+
+    /// Blocks until synthesis has started, see `start_speaking_text_async`.
+    pub fn start_speaking_text(&self, text: impl AsRef<str>) -> Result<SpeechSynthesisResult, SpxError> {
+        self.speak(text, synthesizer_start_speaking_text)
+    }
+
+    /// Blocks until synthesis has started, see `start_speaking_text_async`.
+    pub fn start_speaking_ssml(&self, text: impl AsRef<str>) -> Result<SpeechSynthesisResult, SpxError> {
+        self.speak(text, synthesizer_start_speaking_ssml)
+    }
+
+    /// Blocks until the whole text is synthesized.
+    pub fn speak_text(&self, text: impl AsRef<str>) -> Result<SpeechSynthesisResult, SpxError> {
+        self.speak(text, synthesizer_speak_text)
+    }
+
+    /// Blocks until the whole SSML document is synthesized.
+    pub fn speak_ssml(&self, text: impl AsRef<str>) -> Result<SpeechSynthesisResult, SpxError> {
+        self.speak(text, synthesizer_speak_ssml)
+    }
+
+    #[inline]
+    fn speak(&self, text: impl AsRef<str>, f: SpeakFn) -> Result<SpeechSynthesisResult, SpxError> {
+        let text_len = text.as_ref().len();
+        let c_str = CString::new(text.as_ref())?;
+        let mut handle = SPXHANDLE_INVALID;
+        unsafe {
+            convert_err(f(self.handle.get(), c_str.as_ptr(), text_len as u32, &mut handle))?;
+        }
+        SpeechSynthesisResult::from_handle(handle)
+    }
+
     #[inline]
     fn speak_async(&self, text: impl AsRef<str>, f: SpeakAsyncFn)
                    -> Result<AsyncResultHandle<SpeakAsyncStart, SpeechSynthesisResult>, SpxError> {
@@ -83,8 +217,62 @@ impl SpeechSynthesizer {
     }
 }
 
+impl SpeechSynthesizer {
+    fn set_callback(&self,
+                    sender: &Option<Box<Sender<SpeechSynthesisEvent>>>,
+                    f: SynthesizerSetCallbackFn) -> Result<(), SpxError> {
+        if let Some(s) = sender {
+            let s = s.as_ref();
+            unsafe {
+                convert_err(f(self.handle.get(), Some(Self::cb_send), s as *const _ as *mut c_void))?;
+            }
+        } else {
+            unsafe {
+                convert_err(f(self.handle.get(), None, ptr::null_mut()))?;
+            }
+        }
+        Ok(())
+    }
+
+    unsafe extern "C" fn cb_send(_hsynth: SPXSYNTHHANDLE, h_evt: SPXEVENTHANDLE, p_sender: *mut c_void) {
+        let sender = &mut *(p_sender as *mut Sender<SpeechSynthesisEvent>);
+        let event = match SpeechSynthesisEvent::create(h_evt) {
+            Ok(x) => x,
+            Err(e) => {
+                error!("can not create event, err: {}", e);
+                return;
+            }
+        };
+        match sender.try_send(event) {
+            Ok(()) => {}
+            Err(e) => {
+                error!("can not publish event, err: {}", e);
+            }
+        }
+    }
+}
+
+impl Drop for SpeechSynthesizer {
+    fn drop(&mut self) {
+        // pending async handles may keep the native synthesizer alive after the senders are dropped
+        let callbacks: [(bool, SynthesizerSetCallbackFn); 4] = [
+            (self.started_sender.is_some(), synthesizer_started_set_callback),
+            (self.synthesizing_sender.is_some(), synthesizer_synthesizing_set_callback),
+            (self.completed_sender.is_some(), synthesizer_completed_set_callback),
+            (self.canceled_sender.is_some(), synthesizer_canceled_set_callback),
+        ];
+        for (connected, f) in callbacks.iter() {
+            if *connected {
+                if let Err(e) = self.set_callback(&None, *f) {
+                    error!("can not disconnect synthesizer callback, err: {}", e);
+                }
+            }
+        }
+    }
+}
+
 pub struct SpeakAsyncStart {
-    handle: Arc<SmartHandle<SPXSYNTHHANDLE>>,
+    handle: Arc<SynthesizerHandle>,
     f: SpeakAsyncFn,
     text: CString,
     text_len: usize,
@@ -132,6 +320,63 @@ impl SpeechSynthesisResult {
         Ok(buff.into_vec(filled_size as usize))
     }
 
+    /// Format of `audio_data`, e.g. to create an audio stream of the same format.
+    pub fn audio_stream_format(&self) -> Result<AudioStreamFormat, SpxError> {
+        let handle = crate::spx_populate(self.get_handle(), synth_result_get_audio_format)?;
+        Ok(AudioStreamFormat::from_handle(handle))
+    }
+
+    /// Output format of the synthesizer which produced this result, `None` if the result does not carry it.
+    pub fn output_format(&self) -> Result<Option<SpeechSynthesisOutputFormat>, SpxError> {
+        let props = PropertyBag::create(self.get_handle(), synth_result_get_property_bag)?;
+        props.get(PropertyId::SpeechServiceConnectionSynthOutputFormat)?
+            .filter(|x| !x.is_empty())
+            .map(|x| x.parse())
+            .transpose()
+    }
+
+    /// Sample rate, bits per sample, channels, container and codec of `audio_data`.
+    ///
+    /// The Speech SDK has no accessors for the fields of `audio_stream_format`, RIFF audio is described
+    /// by its wave header and raw audio by `output_format`. Fails with `SpxError::UnknownOutputFormat`
+    /// if neither is available.
+    pub fn audio_format(&self) -> Result<AudioFormatDescriptor, SpxError> {
+        let mut header = [0u8; 12];
+        let mut stream = self.audio_data_stream()?;
+        let mut filled = 0;
+        while filled < header.len() {
+            let n = stream.read_data(&mut header[filled..])?;
+            if n == 0 {
+                break;
+            }
+            filled += n;
+        }
+        if &header[..4] == b"RIFF" && &header[8..] == b"WAVE" {
+            return wav::read_fmt(&mut (&header[..]).chain(stream))?.descriptor();
+        }
+        match self.output_format()? {
+            Some(format) if format.descriptor().container == AudioContainer::Raw => Ok(format.descriptor()),
+            Some(format) => Err(SpxError::UnknownOutputFormat(format!("{} without a RIFF header", format))),
+            None => Err(SpxError::UnknownOutputFormat("neither a RIFF header nor an output format is available".to_owned())),
+        }
+    }
+
+    /// Incremental and seekable access to the audio, unlike `audio_data` nothing is copied up front.
+    /// For results of `start_speaking_*` reads block until more audio has been synthesized.
+    pub fn audio_data_stream(&self) -> Result<AudioDataStream, SpxError> {
+        AudioDataStream::from_result(self)
+    }
+
+    /// `audio_data` of a completed synthesis, `SpxError::SynthesisCanceled` if it has been canceled.
+    pub(crate)
+    fn completed_audio_data(&self) -> Result<Vec<u8>, SpxError> {
+        if self.reason()? == ResultReason::Canceled {
+            let details = self.cancellation_details()?;
+            return Err(SpxError::SynthesisCanceled(details.reason, details.err_code));
+        }
+        self.audio_data()
+    }
+
     pub fn cancellation_details(&self) -> Result<SpeechSynthesisCancellationDetails, SpxError> {
         let reason = {
             let code = crate::spx_populate(self.get_handle(), synth_result_get_reason_canceled)?;
@@ -183,3 +428,39 @@ pub struct SpeechSynthesisCancellationDetails {
     pub reason: CancellationReason,
     pub err_code: CancellationErrorCode,
 }
+
+// SpeechSynthesisEvent
+
+pub struct SpeechSynthesisEvent {
+    result: SpeechSynthesisResult,
+    handle: SmartHandle<SPXEVENTHANDLE>,
+}
+
+impl SpeechSynthesisEvent {
+    #[inline]
+    pub fn get_handle(&self) -> SPXEVENTHANDLE {
+        self.handle.get()
+    }
+
+    /// For `synthesizing` events `audio_data` only holds the latest chunk.
+    pub fn result(&self) -> &SpeechSynthesisResult {
+        &self.result
+    }
+
+    pub fn into_result(self) -> SpeechSynthesisResult {
+        self.result
+    }
+}
+
+impl EventFactory for SpeechSynthesisEvent {
+    fn create(handle: SPXEVENTHANDLE) -> Result<SpeechSynthesisEvent, SpxError> {
+        let handle = SmartHandle::create("SpeechSynthesisEvent", handle, synthesizer_event_handle_release);
+        let result_handle = crate::spx_populate(handle.get(), synthesizer_synthesis_event_get_result)?;
+        Ok(SpeechSynthesisEvent {
+            result: SpeechSynthesisResult::from_handle(result_handle)?,
+            handle,
+        })
+    }
+}
+
+unsafe impl Send for SpeechSynthesisEvent {}
@@ -0,0 +1,69 @@
+// These tests use the native Speech SDK, a subscription is not required: without valid credentials
+// the synthesis ends with a canceled result.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use tokio::runtime::current_thread::Runtime;
+
+use microsoft_speech::audio::{AudioConfig, AudioOutputStream, PushAudioOutputStreamCallback};
+use microsoft_speech::SpeechConfig;
+use microsoft_speech::synthesizer::SpeechSynthesizer;
+
+#[derive(Default)]
+struct State {
+    written: AtomicUsize,
+    closed: AtomicBool,
+    dropped: AtomicBool,
+}
+
+struct Callback(Arc<State>);
+
+impl PushAudioOutputStreamCallback for Callback {
+    fn write(&mut self, data_buffer: &[u8]) -> usize {
+        assert!(!self.0.dropped.load(Ordering::SeqCst));
+        self.0.written.fetch_add(data_buffer.len(), Ordering::SeqCst);
+        data_buffer.len()
+    }
+
+    fn close(&mut self) {
+        assert!(!self.0.dropped.load(Ordering::SeqCst));
+        self.0.closed.store(true, Ordering::SeqCst);
+    }
+}
+
+impl Drop for Callback {
+    fn drop(&mut self) {
+        self.0.dropped.store(true, Ordering::SeqCst);
+    }
+}
+
+fn push_stream_synthesizer(state: &Arc<State>) -> SpeechSynthesizer {
+    let config = SpeechConfig::from_subscription("key", "westus").unwrap();
+    let stream = <dyn AudioOutputStream>::create_push_stream(Callback(state.clone()), None).unwrap();
+    let audio = AudioConfig::output_to_stream(stream).unwrap();
+    SpeechSynthesizer::from_config(config, Some(audio)).unwrap()
+}
+
+#[test]
+fn push_stream_callback_outlives_pending_speak() {
+    let state = Arc::new(State::default());
+    let synthesizer = push_stream_synthesizer(&state);
+    let pending = synthesizer.speak_text_async("hello").unwrap();
+    drop(synthesizer);
+    assert!(!state.dropped.load(Ordering::SeqCst));
+
+    let mut runtime = Runtime::new().unwrap();
+    // canceled or failed, the callback must stay valid either way
+    let _ = runtime.block_on(pending);
+    assert!(state.dropped.load(Ordering::SeqCst));
+}
+
+#[test]
+fn push_stream_callback_is_dropped_with_synthesizer() {
+    let state = Arc::new(State::default());
+    let synthesizer = push_stream_synthesizer(&state);
+    let _ = synthesizer.speak_text("hello");
+    drop(synthesizer);
+    assert!(state.dropped.load(Ordering::SeqCst));
+}